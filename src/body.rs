@@ -1,30 +1,55 @@
-use std::{collections::HashMap, path::PathBuf};
+use std::{collections::HashMap, fmt::Debug, path::PathBuf, sync::Arc};
 
 use async_trait::async_trait;
 use serde_json::Value;
-use tokio::{fs, io::{AsyncReadExt, AsyncWrite, AsyncWriteExt}};
+use tokio::{fs, io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt}, sync::{mpsc, Mutex}};
 
 use super::{
     split_bytes, 
     split_bytes_once, 
+    codec::{encode_chunk, encode_last_chunk, BodyDecoder, BodyFraming},
     error::HttpError, 
     headers::Headers, 
-    read_line_crlf, 
     Sendable
 };
 
 #[derive(Debug, Clone)]
 pub struct Body {
-    pub data: Vec<u8>
+    pub data: Vec<u8>,
+    /// Source of body streamed when sending, instead of the data
+    pub stream: Option<BodyStream>
 }
 
 impl Body {
     pub fn new(data: Vec<u8>) -> Body {
         Body {
-            data
+            data,
+            stream: None
         }
     }
 
+    /// Create body streamed from the source, for bodies with length unknown before sending
+    ///
+    /// It is sent with chunked transfer encoding and trailers of the source
+    /// (or until connection is closed, if `Transfer-Encoding` is removed from the message headers). \
+    /// Source is read once, clones of the body share it
+    pub fn from_stream(source: impl BodySource + 'static) -> Body {
+        Body {
+            data: Vec::new(),
+            stream: Some(BodyStream(Arc::new(Mutex::new(Box::new(source)))))
+        }
+    }
+
+    /// Create body streamed from the reader
+    pub fn from_reader(reader: impl AsyncRead + Unpin + Send + 'static) -> Body {
+        Self::from_stream(ReaderSource(reader))
+    }
+
+    /// Is body streamed from a source
+    pub fn is_stream(&self) -> bool {
+        self.stream.is_some()
+    }
+
     pub fn as_bytes(&self) -> Vec<u8> {
         self.data.clone()
     }
@@ -134,26 +159,25 @@ impl Body {
     }
 
     pub async fn recv(stream: &mut (impl AsyncReadExt + Unpin), headers: &Headers) -> Result<Body, HttpError> {
-        let mut reqdata: Vec<u8> = Vec::new();
-
-        if let Some(content_size) = headers.clone().get("content-length".to_string()).get(0) {
-            let content_size: usize = content_size.parse().map_err(|_| HttpError::InvalidContentSize)?;
-            reqdata.resize(content_size, 0);
-            stream.read_exact(&mut reqdata).await.map_err(|_| HttpError::InvalidContent)?;
-        } else if let Some(transfer_encoding) = headers.clone().get("transfer_encoding".to_string()).get(0) {
-            if transfer_encoding.split(",").map(|o| o.trim()).find(|o| o == &"chunked").is_some() {
-                loop {
-                    let length = usize::from_str_radix(&read_line_crlf(stream).await?, 16).map_err(|_| HttpError::InvalidContent)?;
-                    if length == 0 { break }
-                    let mut data = vec![0u8; length+2];
-                    stream.read_exact(&mut data).await.map_err(|_| HttpError::InvalidContent)?;
-                    data.truncate(length);
-                    reqdata.append(&mut data);
-                }
+        Ok(Self::recv_framed(stream, BodyFraming::from_headers(headers)?).await?.0)
+    }
+
+    /// Read body with the given framing, returns body and trailer headers
+    pub async fn recv_framed(stream: &mut (impl AsyncReadExt + Unpin), framing: BodyFraming) -> Result<(Body, Headers), HttpError> {
+        let mut decoder = BodyDecoder::new(framing);
+        let mut buffer = vec![0u8; 8192];
+
+        while !decoder.is_done() {
+            let size = decoder.hint().min(buffer.len());
+            let size = stream.read(&mut buffer[..size]).await.map_err(|_| HttpError::InvalidContent)?;
+            if size == 0 {
+                decoder.finish()?;
+                break;
             }
+            decoder.decode(&buffer[..size])?;
         }
 
-        Ok(Body::from_bytes(&reqdata))
+        Ok(decoder.into_parts())
     }
 }
 
@@ -163,6 +187,9 @@ impl Sendable for Body {
         &self,
         stream: &mut (dyn AsyncWrite + Unpin + Send + Sync),
     ) -> Result<(), HttpError> {
+        if let Some(body) = &self.stream {
+            return body.send(stream, false, &Headers::new()).await;
+        }
         stream.write_all(&self.as_bytes()).await.map_err(|_| HttpError::WriteHeadError)
     }
     fn as_box(self) -> Box<dyn Sendable> {
@@ -173,11 +200,120 @@ impl Sendable for Body {
 impl Default for Body {
     fn default() -> Self {
         Body {
-            data: Vec::new()
+            data: Vec::new(),
+            stream: None
         }
     }
 }
 
+/// Source of streamed body, see [`Body::from_stream`]
+#[async_trait]
+pub trait BodySource: Send {
+    /// Read next part of body, `None` when body is over
+    async fn next_chunk(&mut self) -> Result<Option<Vec<u8>>, HttpError>;
+
+    /// Get trailer headers, called after the last part (like checksum of the streamed data)
+    fn trailers(&mut self) -> Headers {
+        Headers::new()
+    }
+}
+
+/// Body source that reads the reader to the end
+pub struct ReaderSource<R: AsyncRead + Unpin + Send>(pub R);
+
+#[async_trait]
+impl<R: AsyncRead + Unpin + Send> BodySource for ReaderSource<R> {
+    async fn next_chunk(&mut self) -> Result<Option<Vec<u8>>, HttpError> {
+        let mut buffer = vec![0u8; 8192];
+        let size = self.0.read(&mut buffer).await.map_err(|_| HttpError::WriteBodyError)?;
+        if size == 0 {
+            return Ok(None);
+        }
+        buffer.truncate(size);
+        Ok(Some(buffer))
+    }
+}
+
+/// Body is the data received from the channel until all senders are dropped
+#[async_trait]
+impl BodySource for mpsc::Receiver<Vec<u8>> {
+    async fn next_chunk(&mut self) -> Result<Option<Vec<u8>>, HttpError> {
+        Ok(self.recv().await)
+    }
+}
+
+/// Shared body source of [`Body`]
+#[derive(Clone)]
+pub struct BodyStream(Arc<Mutex<Box<dyn BodySource>>>);
+
+impl Debug for BodyStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("BodyStream")
+    }
+}
+
+impl BodyStream {
+    /// Write the whole source with chunked encoding and trailers (added to the given ones),
+    /// or as is if not `chunked`
+    pub async fn send(
+        &self,
+        stream: &mut (dyn AsyncWrite + Unpin + Send + Sync),
+        chunked: bool,
+        trailers: &Headers
+    ) -> Result<(), HttpError> {
+        let mut source = self.0.lock().await;
+
+        if !chunked {
+            while let Some(chunk) = source.next_chunk().await? {
+                stream.write_all(&chunk).await.map_err(|_| HttpError::WriteBodyError)?;
+            }
+            return Ok(());
+        }
+
+        let mut writer = ChunkedWriter::new(stream);
+        while let Some(chunk) = source.next_chunk().await? {
+            writer.write_chunk(&chunk).await?;
+        }
+        let mut trailers = trailers.clone();
+        for (key, value) in source.trailers().entries() {
+            trailers.add(key, value);
+        }
+        writer.finish(&trailers).await?;
+        Ok(())
+    }
+}
+
+/// Writer of chunked body, for bodies with length unknown before sending
+///
+/// Write the message head with `Transfer-Encoding: chunked` first,
+/// then write chunks as they are produced and finish with optional trailers
+pub struct ChunkedWriter<W: AsyncWrite + Unpin> {
+    stream: W
+}
+
+impl<W: AsyncWrite + Unpin> ChunkedWriter<W> {
+    pub fn new(stream: W) -> Self {
+        ChunkedWriter {
+            stream
+        }
+    }
+
+    /// Write one chunk, empty data is skipped
+    pub async fn write_chunk(&mut self, data: &[u8]) -> Result<(), HttpError> {
+        let mut buf = Vec::new();
+        encode_chunk(data, &mut buf);
+        self.stream.write_all(&buf).await.map_err(|_| HttpError::WriteBodyError)
+    }
+
+    /// Write last chunk with trailer headers and return the stream
+    pub async fn finish(mut self, trailers: &Headers) -> Result<W, HttpError> {
+        let mut buf = Vec::new();
+        encode_last_chunk(trailers, &mut buf);
+        self.stream.write_all(&buf).await.map_err(|_| HttpError::WriteBodyError)?;
+        Ok(self.stream)
+    }
+}
+
 #[derive(Clone,Debug)]
 pub struct Part {
     pub name: String,
@@ -1,51 +1,98 @@
-use std::time::Duration;
+use std::{collections::HashMap, net::SocketAddr, path::{Path, PathBuf}, sync::{Arc, OnceLock}, time::Duration};
 
-use crate::{error::HttpError, headers::Headers, prelude::HttpResponse, request::IntoRequest};
+use crate::{error::HttpError, headers::Headers, prelude::HttpResponse, request::{IntoRequest, RootURL}};
 
-use super::{send_request, Proxy};
+use super::{resolver::OverrideResolver, send_request, certificates_from_file, certificates_from_pem, CertificatePin, Identity, Connector, EnvProxy, Proxy, ProxyRules, ProxyConnector, Resolver, SystemResolver, TlsConfig, TlsConnector, TlsVersion, UnixConnector};
+
+/// Callback for interim (1xx) responses
+pub type InterimHandler = Arc<dyn Fn(&HttpResponse) + Send + Sync>;
 
 /// Client that sends http requests
 pub struct HttpClient {
+    connector: Option<Arc<dyn Connector>>,
+    resolver: Arc<dyn Resolver>,
+    overrides: HashMap<String, Vec<SocketAddr>>,
     proxy: Proxy,
-    ssl_verify: bool,
+    env_proxy: Option<EnvProxy>,
+    proxy_rules: Option<ProxyRules>,
+    unix_socket: Option<PathBuf>,
+    tls: TlsConfig,
+    proxy_tls: TlsConfig,
     headers: Headers,
     connect_timeout: Option<Duration>, 
     write_timeout: Option<Duration>, 
-    read_timeout: Option<Duration>
+    read_timeout: Option<Duration>,
+    expect_continue: Option<usize>,
+    expect_continue_timeout: Duration,
+    interim_handler: Option<InterimHandler>,
+    tls_connector: OnceLock<Option<TlsConnector>>,
+    proxy_tls_connector: OnceLock<Option<TlsConnector>>
 }
 
 /// [`HttpClient`](HttpClient) builder
 pub struct ClientBuilder {
+    connector: Option<Arc<dyn Connector>>,
+    resolver: Arc<dyn Resolver>,
+    overrides: HashMap<String, Vec<SocketAddr>>,
     proxy: Proxy,
-    ssl_verify: bool,
+    env_proxy: Option<EnvProxy>,
+    proxy_rules: Option<ProxyRules>,
+    unix_socket: Option<PathBuf>,
+    tls: TlsConfig,
+    proxy_tls: TlsConfig,
     headers: Headers,
     connect_timeout: Option<Duration>, 
     write_timeout: Option<Duration>, 
-    read_timeout: Option<Duration>
+    read_timeout: Option<Duration>,
+    expect_continue: Option<usize>,
+    expect_continue_timeout: Duration,
+    interim_handler: Option<InterimHandler>
 }
 
 impl ClientBuilder {
     /// Create a client builder
     pub fn new() -> ClientBuilder {
         ClientBuilder {
+            connector: None,
+            resolver: Arc::new(SystemResolver),
+            overrides: HashMap::new(),
             proxy: Proxy::None,
-            ssl_verify: true,
+            env_proxy: None,
+            proxy_rules: None,
+            unix_socket: None,
+            tls: TlsConfig::default(),
+            proxy_tls: TlsConfig { session_cache: false, ..TlsConfig::default() },
             headers: Headers::new(),
             connect_timeout: None, 
             write_timeout: None, 
-            read_timeout: None
+            read_timeout: None,
+            expect_continue: None,
+            expect_continue_timeout: Duration::from_secs(1),
+            interim_handler: None
         }
     }
 
     /// Build a client
     pub fn build(self) -> HttpClient {
         HttpClient { 
+            connector: self.connector,
+            resolver: self.resolver,
+            overrides: self.overrides,
             proxy: self.proxy, 
-            ssl_verify: self.ssl_verify, 
+            env_proxy: self.env_proxy,
+            proxy_rules: self.proxy_rules,
+            unix_socket: self.unix_socket,
+            tls: self.tls,
+            proxy_tls: self.proxy_tls,
             headers: self.headers,
             connect_timeout: self.connect_timeout,
             write_timeout: self.write_timeout,
-            read_timeout: self.read_timeout
+            read_timeout: self.read_timeout,
+            expect_continue: self.expect_continue,
+            expect_continue_timeout: self.expect_continue_timeout,
+            interim_handler: self.interim_handler,
+            tls_connector: OnceLock::new(),
+            proxy_tls_connector: OnceLock::new()
         }
     }
 
@@ -75,15 +122,166 @@ impl ClientBuilder {
         self
     }
 
+    /// Send `Expect: 100-continue` with bodies of at least `min_size` bytes
+    ///
+    /// Body is sent only after `100 Continue` response or timeout. \
+    /// Requests without body never send it, streamed bodies always do
+    pub fn expect_continue(mut self, min_size: usize) -> Self {
+        self.expect_continue = Some(min_size);
+        self
+    }
+
+    /// Set how long to wait for `100 Continue` before sending the body (1 second by default)
+    pub fn expect_continue_timeout(mut self, timeout: Duration) -> Self {
+        self.expect_continue_timeout = timeout;
+        self
+    }
+
+    /// Set callback for interim (1xx) responses, like `103 Early Hints`
+    ///
+    /// They are skipped if not set
+    pub fn interim_handler(mut self, handler: impl Fn(&HttpResponse) + Send + Sync + 'static) -> Self {
+        self.interim_handler = Some(Arc::new(handler));
+        self
+    }
+
+    /// Set connector that opens streams to the sites
+    ///
+    /// Proxy and unix socket are not used with it
+    pub fn connector(mut self, connector: impl Connector + 'static) -> Self {
+        self.connector = Some(Arc::new(connector));
+        self
+    }
+
+    /// Set resolver of site hosts
+    pub fn resolver(mut self, resolver: impl Resolver + 'static) -> Self {
+        self.resolver = Arc::new(resolver);
+        self
+    }
+
+    /// Connect to the addresses instead of resolving the host
+    ///
+    /// Port of the addresses is used instead of the url port,
+    /// `Host` header and ssl still use the host name
+    pub fn resolve(mut self, host: &str, addrs: Vec<SocketAddr>) -> Self {
+        self.overrides.insert(host.to_lowercase(), addrs);
+        self
+    }
+
     /// Set client proxy
     pub fn proxy(mut self, proxy: Proxy) -> Self {
         self.proxy = proxy;
         self
     }
 
+    /// Select proxies for each site by rules, instead of the client proxy and environment proxies
+    pub fn proxy_rules(mut self, rules: ProxyRules) -> Self {
+        self.proxy_rules = Some(rules);
+        self
+    }
+
+    /// Use proxies from environment variables instead of the client proxy
+    pub fn env_proxy(mut self, proxy: EnvProxy) -> Self {
+        self.env_proxy = Some(proxy);
+        self
+    }
+
+    /// Read proxies from `HTTP_PROXY`, `HTTPS_PROXY`, `ALL_PROXY` and `NO_PROXY` variables,
+    /// they are used instead of the client proxy
+    pub fn proxy_from_env(self) -> Self {
+        self.env_proxy(EnvProxy::from_env())
+    }
+
+    /// Set unix socket to connect to instead of the url host
+    ///
+    /// Url host is still used in `Host` header and for ssl, proxy is not used
+    pub fn unix_socket(mut self, path: impl Into<PathBuf>) -> Self {
+        self.unix_socket = Some(path.into());
+        self
+    }
+
     /// Set is client have to verify ssl certificate
     pub fn ssl_verify(mut self, verify: bool) -> Self {
-        self.ssl_verify = verify;
+        self.tls.verify = verify;
+        self
+    }
+
+    /// Set ssl settings of the sites
+    pub fn tls(mut self, tls: TlsConfig) -> Self {
+        self.tls = tls;
+        self
+    }
+
+    /// Set is client have to trust system root certificates
+    pub fn system_roots(mut self, enabled: bool) -> Self {
+        self.tls.system_roots = enabled;
+        self
+    }
+
+    /// Add trusted root certificates from pem
+    pub fn root_certificates_pem(mut self, pem: &[u8]) -> Result<Self, HttpError> {
+        self.tls.root_certificates.extend(certificates_from_pem(pem)?);
+        Ok(self)
+    }
+
+    /// Add trusted root certificates from pem file
+    pub fn root_certificates_file(mut self, path: impl AsRef<Path>) -> Result<Self, HttpError> {
+        self.tls.root_certificates.extend(certificates_from_file(path)?);
+        Ok(self)
+    }
+
+    /// Set minimal tls version
+    pub fn min_tls_version(mut self, version: TlsVersion) -> Self {
+        self.tls.min_version = Some(version);
+        self
+    }
+
+    /// Set maximal tls version
+    pub fn max_tls_version(mut self, version: TlsVersion) -> Self {
+        self.tls.max_version = Some(version);
+        self
+    }
+
+    /// Set cipher list for tls 1.2 and older, in openssl format
+    pub fn ciphers(mut self, ciphers: impl ToString) -> Self {
+        self.tls.ciphers = Some(ciphers.to_string());
+        self
+    }
+
+    /// Set cipher suites for tls 1.3, in openssl format
+    pub fn ciphersuites(mut self, ciphersuites: impl ToString) -> Self {
+        self.tls.ciphersuites = Some(ciphersuites.to_string());
+        self
+    }
+
+    /// Set is client have to resume tls sessions of hosts (enabled by default)
+    pub fn tls_session_cache(mut self, enabled: bool) -> Self {
+        self.tls.session_cache = enabled;
+        self
+    }
+
+    /// Pin certificate for the host, connections to it fail with
+    /// [`CertificatePinError`](HttpError::CertificatePinError) if none of its pins match
+    pub fn pin_certificate(mut self, host: &str, pin: CertificatePin) -> Self {
+        self.tls.pin(host, pin);
+        self
+    }
+
+    /// Set client certificate for mutual tls
+    pub fn identity(mut self, identity: Identity) -> Self {
+        self.tls.identity = Some(identity);
+        self
+    }
+
+    /// Set is client have to verify ssl certificate of https proxies
+    pub fn proxy_ssl_verify(mut self, verify: bool) -> Self {
+        self.proxy_tls.verify = verify;
+        self
+    }
+
+    /// Set ssl settings of https proxies, like their trusted root certificates
+    pub fn proxy_tls(mut self, tls: TlsConfig) -> Self {
+        self.proxy_tls = tls;
         self
     }
 
@@ -108,15 +306,7 @@ impl HttpClient {
 
     /// Sends a request and receives a response
     pub async fn send(&self, request: impl IntoRequest) -> Result<HttpResponse, HttpError> {
-        send_request(
-            request.to_request()?, 
-            self.ssl_verify, 
-            self.proxy.clone(), 
-            self.headers.clone(),
-            self.connect_timeout,
-            self.write_timeout,
-            self.read_timeout
-        ).await
+        send_request(request.to_request()?, self).await
     }
 
     /// Get connect timeout
@@ -134,14 +324,110 @@ impl HttpClient {
         self.write_timeout.clone()
     }
 
+    /// Get minimal body size to send `Expect: 100-continue` with
+    pub fn expect_continue(&self) -> Option<usize> {
+        self.expect_continue
+    }
+
+    /// Get how long to wait for `100 Continue` before sending the body
+    pub fn expect_continue_timeout(&self) -> Duration {
+        self.expect_continue_timeout
+    }
+
+    /// Get callback for interim (1xx) responses
+    pub fn interim_handler(&self) -> Option<InterimHandler> {
+        self.interim_handler.clone()
+    }
+
+    /// Get connector that opens streams to the site
+    ///
+    /// If it is not set, connector is made of unix socket or proxy for the site
+    pub fn connector(&self, root: &RootURL) -> Arc<dyn Connector> {
+        if let Some(connector) = &self.connector {
+            connector.clone()
+        } else if let Some(path) = &self.unix_socket {
+            Arc::new(UnixConnector(path.clone()))
+        } else {
+            Arc::new(ProxyConnector {
+                proxies: self.proxies_for(root),
+                resolver: self.resolver(),
+                tls: self.proxy_tls_connector().ok()
+            })
+        }
+    }
+
+    /// Get resolver of site hosts, with the static addresses applied
+    pub fn resolver(&self) -> Arc<dyn Resolver> {
+        if self.overrides.is_empty() {
+            self.resolver.clone()
+        } else {
+            Arc::new(OverrideResolver { overrides: self.overrides.clone(), inner: self.resolver.clone() })
+        }
+    }
+
     /// Get client proxy
     pub fn proxy(&self) -> Proxy {
         self.proxy.clone()
     }
 
+    /// Get proxies from environment variables
+    pub fn env_proxy(&self) -> Option<EnvProxy> {
+        self.env_proxy.clone()
+    }
+
+    /// Get rules of proxy selection
+    pub fn proxy_rules(&self) -> Option<ProxyRules> {
+        self.proxy_rules.clone()
+    }
+
+    /// Get proxies for the site, in order of trying
+    ///
+    /// They are selected by proxy rules, then environment proxies, then the client proxy
+    pub fn proxies_for(&self, root: &RootURL) -> Vec<Proxy> {
+        if let Some(rules) = &self.proxy_rules {
+            rules.proxies_for(root)
+        } else if let Some(env_proxy) = &self.env_proxy {
+            vec![env_proxy.proxy_for(root)]
+        } else {
+            vec![self.proxy.clone()]
+        }
+    }
+
+    /// Get unix socket to connect to instead of the url host
+    pub fn unix_socket(&self) -> Option<PathBuf> {
+        self.unix_socket.clone()
+    }
+
     /// Get is client have to verify ssl certificate
     pub fn ssl_verify(&self) -> bool {
-        self.ssl_verify
+        self.tls.verify
+    }
+
+    /// Get ssl settings of the sites
+    pub fn tls(&self) -> TlsConfig {
+        self.tls.clone()
+    }
+
+    /// Get tls connector of the sites, it is built from the ssl settings once and reused
+    ///
+    /// Fails with [`SslError`](HttpError::SslError) if settings are invalid (like unknown ciphers)
+    pub fn tls_connector(&self) -> Result<TlsConnector, HttpError> {
+        self.tls_connector.get_or_init(|| self.tls.connector().ok()).clone().ok_or(HttpError::SslError)
+    }
+
+    /// Get is client have to verify ssl certificate of https proxies
+    pub fn proxy_ssl_verify(&self) -> bool {
+        self.proxy_tls.verify
+    }
+
+    /// Get ssl settings of https proxies
+    pub fn proxy_tls(&self) -> TlsConfig {
+        self.proxy_tls.clone()
+    }
+
+    /// Get tls connector of https proxies, it is built from their ssl settings once and reused
+    pub fn proxy_tls_connector(&self) -> Result<TlsConnector, HttpError> {
+        self.proxy_tls_connector.get_or_init(|| self.proxy_tls.connector().ok()).clone().ok_or(HttpError::SslError)
     }
 
     /// Get default headers
@@ -150,6 +436,14 @@ impl HttpClient {
     }
 }
 
+impl HttpClient {
+    pub(super) fn on_interim(&self, response: &HttpResponse) {
+        if let Some(handler) = &self.interim_handler {
+            handler(response);
+        }
+    }
+}
+
 impl Default for HttpClient {
     /// Create default HttpClient
     fn default() -> Self {
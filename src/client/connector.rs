@@ -0,0 +1,243 @@
+use std::{net::SocketAddr, path::PathBuf, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use base64::{prelude::BASE64_STANDARD, Engine};
+use tokio::{io::{AsyncRead, AsyncWrite, AsyncWriteExt}, net::TcpStream, task::JoinSet};
+#[cfg(unix)]
+use tokio::net::UnixStream;
+use tokio_socks::{tcp::{Socks4Stream, Socks5Stream}, IntoTargetAddr, TargetAddr};
+
+use crate::{codec::encode_headers, error::HttpError, headers::Headers, request::RootURL, response::HttpResponse};
+
+use super::{proxy::split_host_port, Proxy, TlsConfig, TlsConnector, Resolver, SystemResolver};
+
+/// Stream that requests are sent over
+pub trait ClientStream: AsyncRead + AsyncWrite + Unpin + Send + Sync {}
+impl<T: AsyncRead + AsyncWrite + Unpin + Send + Sync> ClientStream for T {}
+
+/// How long to wait for connection attempt before starting the next one
+const ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+/// Stream opened by [`Connector`]
+pub struct Connection {
+    pub stream: Box<dyn ClientStream>,
+    /// Address the stream is connected to, if it is known
+    pub remote_addr: Option<SocketAddr>,
+    /// Stream goes to http proxy that forwards requests,
+    /// they are sent with absolute url and these headers (like `Proxy-Authorization`)
+    pub forward_proxy: Option<Headers>
+}
+
+impl Connection {
+    /// Create connection with unknown address
+    pub fn new(stream: impl ClientStream + 'static) -> Self {
+        Connection { stream: Box::new(stream), remote_addr: None, forward_proxy: None }
+    }
+
+    /// Set address the stream is connected to
+    pub fn remote_addr(mut self, addr: SocketAddr) -> Self {
+        self.remote_addr = Some(addr);
+        self
+    }
+
+    /// Set that stream goes to http proxy that forwards requests with the headers
+    pub fn forward_proxy(mut self, headers: Headers) -> Self {
+        self.forward_proxy = Some(headers);
+        self
+    }
+}
+
+/// Opens streams to the sites for [`HttpClient`](super::HttpClient)
+///
+/// Ssl is added on top of the stream for `https` urls
+#[async_trait]
+pub trait Connector: Send + Sync {
+    /// Connect to the site root (scheme, domain and port)
+    async fn connect(&self, root: &RootURL) -> Result<Connection, HttpError>;
+}
+
+/// Connector that opens tcp connections, directly or through the proxies
+///
+/// Proxies are tried in order until one connects,
+/// proxy hosts and site hosts (when connecting directly) are resolved by the resolver
+#[derive(Clone)]
+pub struct ProxyConnector {
+    pub proxies: Vec<Proxy>,
+    pub resolver: Arc<dyn Resolver>,
+    /// Tls connector of https proxies, they fail with [`SslError`](HttpError::SslError) without it
+    pub tls: Option<TlsConnector>
+}
+
+impl ProxyConnector {
+    /// Create connector with one proxy, system resolver and default ssl settings of https proxies
+    pub fn new(proxy: Proxy) -> Self {
+        let tls = TlsConfig { session_cache: false, ..TlsConfig::default() }.connector().ok();
+        ProxyConnector { proxies: vec![proxy], resolver: Arc::new(SystemResolver), tls }
+    }
+
+    /// Resolve host (`host:port`) and connect to it
+    async fn connect_host(&self, host: &str) -> Result<(TcpStream, SocketAddr), HttpError> {
+        let (name, port) = split_host_port(host);
+        let addrs = self.resolver.resolve(name, port.ok_or(HttpError::UrlError)?).await?;
+        connect_happy_eyeballs(&addrs).await
+    }
+
+    async fn connect_proxy(&self, proxy: Proxy, root: &RootURL) -> Result<Connection, HttpError> {
+        Ok(match proxy {
+            Proxy::Http { host, auth } => {
+                let (stream, addr) = self.connect_host(&host).await?;
+                through_http_proxy(stream, root, auth).await?.remote_addr(addr)
+            }
+            Proxy::Https { host, auth } => {
+                let tls = self.tls.as_ref().ok_or(HttpError::SslError)?;
+                let (stream, addr) = self.connect_host(&host).await?;
+                let stream = tls.connect(split_host_port(&host).0, stream).await?;
+                through_http_proxy(stream, root, auth).await?.remote_addr(addr)
+            }
+            Proxy::Socks4 { host, user, remote_dns } => {
+                let site_host = self.socks_target(root, remote_dns, true).await?;
+                let (stream, addr) = self.connect_host(&host).await?;
+                Connection::new(match user {
+                    Some(user) => Socks4Stream::connect_with_userid_and_socket(stream, site_host, &user).await.map_err(|_| HttpError::ConnectError)?,
+                    None => Socks4Stream::connect_with_socket(stream, site_host).await.map_err(|_| HttpError::ConnectError)?,
+                }).remote_addr(addr)
+            }
+            Proxy::Socks5 { host, auth, remote_dns } => {
+                let site_host = self.socks_target(root, remote_dns, false).await?;
+                let (stream, addr) = self.connect_host(&host).await?;
+                Connection::new(match auth {
+                    Some((u, p)) => Socks5Stream::connect_with_password_and_socket(stream, site_host, &u, &p).await.map_err(|_| HttpError::ConnectError)?,
+                    None => Socks5Stream::connect_with_socket(stream, site_host).await.map_err(|_| HttpError::ConnectError)?,
+                }).remote_addr(addr)
+            }
+            Proxy::None => {
+                let addrs = self.resolver.resolve(&root.domain, root.port).await?;
+                let (stream, addr) = connect_happy_eyeballs(&addrs).await?;
+                Connection::new(stream).remote_addr(addr)
+            }
+        })
+    }
+
+    /// Get site address to send to socks proxy
+    ///
+    /// Site host is resolved by the client resolver, unless the proxy resolves it. Socks4 supports only ipv4 addresses
+    async fn socks_target(&self, root: &RootURL, remote_dns: bool, ipv4_only: bool) -> Result<TargetAddr<'static>, HttpError> {
+        if remote_dns {
+            return format!("{}:{}", root.domain, root.port).into_target_addr().map_err(|_| HttpError::UrlError);
+        }
+        let addrs = self.resolver.resolve(&root.domain, root.port).await?;
+        addrs.into_iter()
+            .find(|o| !ipv4_only || o.is_ipv4())
+            .map(TargetAddr::Ip)
+            .ok_or(HttpError::ConnectError)
+    }
+}
+
+#[async_trait]
+impl Connector for ProxyConnector {
+    async fn connect(&self, root: &RootURL) -> Result<Connection, HttpError> {
+        let mut result = Err(HttpError::ConnectError);
+        for proxy in &self.proxies {
+            result = self.connect_proxy(proxy.clone(), root).await;
+            if result.is_ok() {
+                break;
+            }
+        }
+        result
+    }
+}
+
+/// Send requests through http proxy
+///
+/// Plain http requests are forwarded by the proxy, for others tunnel is opened with `CONNECT` request
+async fn through_http_proxy<S: ClientStream + 'static>(
+    mut stream: S,
+    root: &RootURL,
+    auth: Option<(String, String)>
+) -> Result<Connection, HttpError> {
+    let mut headers = Headers::new();
+    if let Some((user, password)) = auth {
+        headers.put("Proxy-Authorization", format!("basic {}", BASE64_STANDARD.encode(format!("{user}:{password}"))));
+    }
+
+    if root.scheme == "http" {
+        return Ok(Connection::new(stream).forward_proxy(headers));
+    }
+
+    let site_host = format!("{}:{}", root.domain, root.port);
+    let mut connect_request = format!("CONNECT {site_host} HTTP/1.1\r\nHost: {site_host}\r\n").into_bytes();
+    encode_headers(&headers, &mut connect_request);
+    connect_request.extend_from_slice(b"\r\n");
+
+    stream.write_all(&connect_request).await.map_err(|_| HttpError::ConnectError)?;
+    let response = HttpResponse::recv_final(&mut stream, Some("CONNECT"), |_| {}).await.map_err(|_| HttpError::ConnectError)?;
+    if !response.code().is_some_and(|o| (200..300).contains(&o)) {
+        return Err(HttpError::ProxyConnectError);
+    }
+
+    Ok(Connection::new(stream))
+}
+
+/// Connect to the first address that answers (RFC 8305, Happy Eyeballs)
+///
+/// Addresses are tried alternating ipv6 and ipv4, next attempt starts when previous one fails
+/// or doesn't connect in 250 ms, started attempts race each other
+pub async fn connect_happy_eyeballs(addrs: &[SocketAddr]) -> Result<(TcpStream, SocketAddr), HttpError> {
+    let mut pending = interleave_families(addrs).into_iter().peekable();
+    let mut attempts = JoinSet::new();
+
+    while pending.peek().is_some() || !attempts.is_empty() {
+        if let Some(addr) = pending.next() {
+            attempts.spawn(async move { (TcpStream::connect(addr).await, addr) });
+        }
+
+        let delay = tokio::time::sleep(ATTEMPT_DELAY);
+        tokio::pin!(delay);
+
+        tokio::select! {
+            Some(result) = attempts.join_next() => {
+                if let Ok((Ok(stream), addr)) = result {
+                    return Ok((stream, addr));
+                }
+            }
+            _ = &mut delay, if pending.peek().is_some() => {}
+        }
+    }
+
+    Err(HttpError::ConnectError)
+}
+
+/// Order addresses alternating families, starting with the family of the first one
+fn interleave_families(addrs: &[SocketAddr]) -> Vec<SocketAddr> {
+    let Some(first) = addrs.first() else { return Vec::new(); };
+    let (mut same, mut other): (Vec<_>, Vec<_>) = addrs.iter().partition(|o| o.is_ipv6() == first.is_ipv6());
+    same.reverse();
+    other.reverse();
+
+    let mut result = Vec::with_capacity(addrs.len());
+    while let Some(addr) = same.pop() {
+        result.push(addr);
+        if let Some(addr) = other.pop() {
+            result.push(addr);
+        }
+    }
+    result.extend(other.into_iter().rev());
+    result
+}
+
+/// Connector that opens connections to the unix socket, whatever the url host is
+#[derive(Clone, Debug)]
+pub struct UnixConnector(pub PathBuf);
+
+#[async_trait]
+impl Connector for UnixConnector {
+    #[cfg(unix)]
+    async fn connect(&self, _: &RootURL) -> Result<Connection, HttpError> {
+        Ok(Connection::new(UnixStream::connect(&self.0).await.map_err(|_| HttpError::ConnectError)?))
+    }
+
+    #[cfg(not(unix))]
+    async fn connect(&self, _: &RootURL) -> Result<Connection, HttpError> {
+        Err(HttpError::ConnectError)
+    }
+}
@@ -1,59 +1,29 @@
-use std::{pin::Pin, time::Duration};
-
-use base64::Engine;
-use openssl::ssl::{SslConnector, SslMethod, SslVerifyMode};
-use tokio::{io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt}, net::TcpStream};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 use tokio_io_timeout::TimeoutStream;
-use tokio_openssl::SslStream;
-use tokio_socks::tcp::{Socks4Stream, Socks5Stream};
-
-use super::{error::HttpError, gen_multipart_boundary, headers::Headers, prelude::HttpResponse, request::HttpRequest, Sendable};
 
-use base64::prelude::BASE64_STANDARD;
+use super::{codec::{encode_request, encode_request_absolute, find_head_end, is_chunked, put_stream_framing}, error::HttpError, gen_multipart_boundary, prelude::HttpResponse, request::HttpRequest};
 
 pub mod req_builder;
 pub mod client;
 pub mod proxy;
+pub mod proxy_rules;
+pub mod connector;
+pub mod resolver;
+pub mod tls;
 
 pub use req_builder::*;
 pub use client::*;
 pub use proxy::*;
-
-trait RequestStream: AsyncRead + AsyncWrite + Unpin + Send + Sync {}
-impl<T: AsyncRead + AsyncWrite + Unpin + Send + Sync> RequestStream for T {}
-
-async fn connect_stream(proxy: Proxy, site_host: &str) -> Result<Box<dyn RequestStream>, HttpError> {
-    Ok(match proxy {
-        Proxy::Http { host, auth } | Proxy::Https { host, auth } => {
-            let mut stream = TcpStream::connect(host).await.map_err(|_| HttpError::ConnectError)?;
-            let auth_header = auth.map(|(u, p)| format!("Proxy-Authorization: basic {}\r\n", BASE64_STANDARD.encode(format!("{u}:{p}"))));
-            let connect_request = format!("CONNECT {site_host} HTTP/1.1\r\nHost: {site_host}\r\n{}\r\n", auth_header.unwrap_or_default());
-            stream.write_all(connect_request.as_bytes()).await.map_err(|_| HttpError::ConnectError)?;
-            HttpResponse::recv(&mut stream).await.map_err(|_| HttpError::ConnectError)?;
-            Box::new(stream)
-        }
-        Proxy::Socks4 { host, user } => Box::new(match user {
-            Some(user) => Socks4Stream::connect_with_userid(host, site_host, &user).await.map_err(|_| HttpError::ConnectError)?,
-            None => Socks4Stream::connect(host, site_host).await.map_err(|_| HttpError::ConnectError)?,
-        }),
-        Proxy::Socks5 { host, auth } => Box::new(match auth {
-            Some((u, p)) => Socks5Stream::connect_with_password(host, site_host, &u, &p).await.map_err(|_| HttpError::ConnectError)?,
-            None => Socks5Stream::connect(host, site_host).await.map_err(|_| HttpError::ConnectError)?,
-        }),
-        Proxy::None => Box::new(TcpStream::connect(site_host).await.map_err(|_| HttpError::ConnectError)?),
-    })
-}
+pub use proxy_rules::*;
+pub use connector::*;
+pub use resolver::*;
+pub use tls::*;
 
 async fn send_request(
     mut request: HttpRequest, 
-    ssl_verify: bool, 
-    proxy: Proxy, 
-    headers: Headers,
-    connect_timeout: Option<Duration>, 
-    write_timeout: Option<Duration>, 
-    read_timeout: Option<Duration>
+    client: &HttpClient
 ) -> Result<HttpResponse, HttpError> {
-    for (key, value) in headers.entries() {
+    for (key, value) in client.headers().entries() {
         request.headers.put_default(key, value);
     }
 
@@ -61,55 +31,97 @@ async fn send_request(
 
     request.headers.put_default("Connection", "close".to_string());
     request.headers.put_default("Host", root.domain.to_string());
-    request.headers.put_default("Content-Length", request.body.as_bytes().len().to_string());
+    put_stream_framing(&mut request.headers, &request.body);
+    if !is_chunked(&request.headers) && !request.body.is_stream() {
+        request.headers.put_default("Content-Length", request.body.as_bytes().len().to_string());
+    }
+    // expectation is sent only with a body (RFC 9110 10.1.1), streamed body is counted as large
+    let body_size = if request.body.is_stream() { usize::MAX } else { request.body.data.len() };
+    if body_size > 0 && client.expect_continue().is_some_and(|o| body_size >= o) {
+        request.headers.put_default("Expect", "100-continue".to_string());
+    }
     
-    let site_host = format!("{}:{}", root.domain, root.port);
-    let stream: Box<dyn RequestStream> = match connect_timeout {
+    let connector = client.connector(&root);
+    let connection = match client.connect_timeout() {
         Some(connect_timeout) => {
             tokio::time::timeout(
                 connect_timeout,
-                connect_stream(proxy, &site_host)
+                connector.connect(&root)
             ).await.map_err(|_| HttpError::ConnectError)??
         }, None => {
-            connect_stream(proxy, &site_host).await?
+            connector.connect(&root).await?
         }
     };
     
-    let mut stream = TimeoutStream::new(stream);
-    stream.set_write_timeout(write_timeout);
-    stream.set_read_timeout(read_timeout);
+    let absolute = connection.forward_proxy.is_some();
+    for (key, value) in connection.forward_proxy.iter().flat_map(|o| o.entries()) {
+        request.headers.put_default(key, value);
+    }
+
+    let mut stream = TimeoutStream::new(connection.stream);
+    stream.set_write_timeout(client.write_timeout());
+    stream.set_read_timeout(client.read_timeout());
     let mut stream = Box::pin(stream);
     
-    if root.scheme == "https" {
-        let mut stream = ssl_wrapper(ssl_verify, root.domain.clone(), stream).await?;
-        request.send(&mut stream).await?;
-        Ok(HttpResponse::recv(&mut stream).await?)
+    let mut response = if root.scheme == "https" {
+        let mut stream = client.tls_connector()?.connect(&root.domain, stream).await?;
+        let mut response = exchange(&request, &mut stream, client, absolute).await?;
+        response.tls = Some(TlsInfo::from_ssl(stream.ssl()));
+        // closing tls properly keeps the session resumable
+        let _ = stream.shutdown().await;
+        response
     } else {
-        request.send(&mut stream).await?;
-        Ok(HttpResponse::recv(&mut stream).await?)
-    }
+        exchange(&request, &mut stream, client, absolute).await?
+    };
+    response.remote_addr = connection.remote_addr;
+    Ok(response)
 }
 
-async fn ssl_wrapper<S: AsyncReadExt + AsyncWriteExt>(ssl_verify: bool, domain: String, stream: S) -> Result<Pin<Box<SslStream<S>>>, HttpError> {
-    let mut ssl_connector = SslConnector::builder(SslMethod::tls())
-        .map_err(|_| HttpError::SslError)?;
-    
-    ssl_connector.set_verify(if ssl_verify { SslVerifyMode::PEER } else { SslVerifyMode::NONE });
+async fn exchange(
+    request: &HttpRequest,
+    stream: &mut (impl AsyncRead + AsyncWrite + Unpin + Send + Sync),
+    client: &HttpClient,
+    absolute: bool
+) -> Result<HttpResponse, HttpError> {
+    let mut data = Vec::new();
+    if absolute {
+        encode_request_absolute(request, &mut data);
+    } else {
+        encode_request(request, &mut data);
+    }
 
-    let ssl_connector = ssl_connector.build();
+    if !request.expects_continue() {
+        stream.write_all(&data).await.map_err(|_| HttpError::WriteBodyError)?;
+        send_stream(request, stream).await?;
+        return HttpResponse::recv_final(stream, Some(&request.method), |o| client.on_interim(o)).await;
+    }
 
-    let ssl = ssl_connector
-        .configure()
-        .map_err(|_| HttpError::SslError)?
-        .into_ssl(&domain)
-        .map_err(|_| HttpError::SslError)?;
+    let (head, body) = data.split_at(find_head_end(&data).unwrap_or(data.len()));
 
-    let wrapper = SslStream::new(ssl, stream)
-        .map_err(|_| HttpError::SslError)?;
+    stream.write_all(head).await.map_err(|_| HttpError::WriteHeadError)?;
 
-    let mut wrapper = Box::pin(wrapper);
+    let mut first = [0u8; 1];
+    // if server is silent until timeout, body is sent anyway
+    if let Ok(result) = tokio::time::timeout(client.expect_continue_timeout(), stream.read_exact(&mut first)).await {
+        result.map_err(|_| HttpError::ReadLineEof)?;
+        let mut response = HttpResponse::recv_head(&mut (&first[..]).chain(&mut *stream)).await?;
+        if response.is_interim() {
+            client.on_interim(&response);
+        } else {
+            response.recv_body(stream, Some(&request.method)).await?;
+            return Ok(response);
+        }
+    }
 
-    wrapper.as_mut().connect().await.map_err(|_| HttpError::SslError)?;
+    stream.write_all(body).await.map_err(|_| HttpError::WriteBodyError)?;
+    send_stream(request, stream).await?;
+    HttpResponse::recv_final(stream, Some(&request.method), |o| client.on_interim(o)).await
+}
 
-    Ok(wrapper)
+/// Write streamed body of the request, if it has one
+async fn send_stream(request: &HttpRequest, stream: &mut (impl AsyncWrite + Unpin + Send + Sync)) -> Result<(), HttpError> {
+    match &request.body.stream {
+        Some(body) => body.send(stream, is_chunked(&request.headers), &request.trailers).await,
+        None => Ok(())
+    }
 }
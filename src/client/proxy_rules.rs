@@ -0,0 +1,122 @@
+use std::net::IpAddr;
+
+use crate::request::RootURL;
+
+use super::{proxy::ip_in_cidr, Proxy};
+
+/// Condition of proxy rule, all set parts have to match
+#[derive(Clone, Debug, Default)]
+pub struct ProxyMatch {
+    scheme: Option<String>,
+    host: Option<String>,
+    /// `Some(None)` is invalid block
+    cidr: Option<Option<(IpAddr, u8)>>
+}
+
+impl ProxyMatch {
+    /// Match any site
+    pub fn any() -> Self {
+        Self::default()
+    }
+
+    /// Match hosts by glob pattern (`*` is any text, `?` is any char), like `*.corp.example.com`
+    pub fn host(pattern: &str) -> Self {
+        Self::any().and_host(pattern)
+    }
+
+    /// Match url scheme, like `https`
+    pub fn scheme(scheme: &str) -> Self {
+        Self::any().and_scheme(scheme)
+    }
+
+    /// Match ip address hosts in CIDR block, like `10.0.0.0/8`
+    ///
+    /// Host names are not resolved for it, so they don't match
+    pub fn cidr(block: &str) -> Self {
+        Self::any().and_cidr(block)
+    }
+
+    /// Also match hosts by glob pattern
+    pub fn and_host(mut self, pattern: &str) -> Self {
+        self.host = Some(pattern.to_lowercase());
+        self
+    }
+
+    /// Also match url scheme
+    pub fn and_scheme(mut self, scheme: &str) -> Self {
+        self.scheme = Some(scheme.to_lowercase());
+        self
+    }
+
+    /// Also match ip address hosts in CIDR block, invalid block matches nothing
+    pub fn and_cidr(mut self, block: &str) -> Self {
+        let (addr, prefix) = block.split_once('/').unwrap_or((block, ""));
+        self.cidr = Some(addr.parse::<IpAddr>().ok().map(|ip| {
+            let max = if ip.is_ipv4() { 32 } else { 128 };
+            (ip, prefix.parse::<u8>().unwrap_or(max).min(max))
+        }));
+        self
+    }
+
+    /// Does site match the condition
+    pub fn matches(&self, root: &RootURL) -> bool {
+        let domain = root.domain.trim_start_matches('[').trim_end_matches(']').to_lowercase();
+
+        self.scheme.as_ref().is_none_or(|o| root.scheme.eq_ignore_ascii_case(o)) &&
+            self.host.as_ref().is_none_or(|o| glob_matches(o.as_bytes(), domain.as_bytes())) &&
+            self.cidr.is_none_or(|o| o.is_some_and(|(net, prefix)|
+                domain.parse::<IpAddr>().is_ok_and(|ip| ip_in_cidr(ip, net, prefix))))
+    }
+}
+
+/// Proxy selection by rules, like PAC file
+///
+/// The first matching rule gives proxies for the site, they are tried in order until one connects
+/// ([`Proxy::None`] connects directly). Sites without matching rule use default proxies
+#[derive(Clone, Debug)]
+pub struct ProxyRules {
+    rules: Vec<(ProxyMatch, Vec<Proxy>)>,
+    default: Vec<Proxy>
+}
+
+impl ProxyRules {
+    /// Create rules, sites are connected directly by default
+    pub fn new() -> Self {
+        ProxyRules { rules: Vec::new(), default: vec![Proxy::None] }
+    }
+
+    /// Add rule, checked after the rules added before
+    pub fn rule(mut self, condition: ProxyMatch, proxies: Vec<Proxy>) -> Self {
+        self.rules.push((condition, proxies));
+        self
+    }
+
+    /// Set proxies for sites without matching rule
+    pub fn default_proxies(mut self, proxies: Vec<Proxy>) -> Self {
+        self.default = proxies;
+        self
+    }
+
+    /// Get proxies for the site, in order of trying
+    pub fn proxies_for(&self, root: &RootURL) -> Vec<Proxy> {
+        self.rules.iter()
+            .find(|o| o.0.matches(root))
+            .map(|o| o.1.clone())
+            .unwrap_or_else(|| self.default.clone())
+    }
+}
+
+impl Default for ProxyRules {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn glob_matches(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some((b'*', rest)) => (0..=text.len()).any(|i| glob_matches(rest, &text[i..])),
+        Some((b'?', rest)) => !text.is_empty() && glob_matches(rest, &text[1..]),
+        Some((c, rest)) => text.first() == Some(c) && glob_matches(rest, &text[1..])
+    }
+}
@@ -2,9 +2,9 @@ use std::collections::HashMap;
 
 use serde_json::Value;
 
-use crate::{error::HttpError, request::{IntoRequest, IntoURL}};
+use crate::{codec::put_stream_framing, error::HttpError, request::{IntoRequest, IntoURL}};
 
-use super::{super::body::{Body, Part}, gen_multipart_boundary, super::headers::Headers, super::request::HttpRequest};
+use super::{super::body::{Body, BodySource, Part}, gen_multipart_boundary, super::headers::Headers, super::request::HttpRequest};
 
 /// Builder for [`HttpRequest`](HttpRequest)
 #[derive(Debug, Clone)]
@@ -13,7 +13,8 @@ pub struct RequestBuilder {
     url: String,
     headers: Headers,
     body: Option<Body>,
-    url_query: Option<HashMap<String, String>>
+    url_query: Option<HashMap<String, String>>,
+    trailers: Headers
 }
 
 impl RequestBuilder {
@@ -24,7 +25,8 @@ impl RequestBuilder {
             url: url.to_string(),
             headers: Headers::new(),
             body: None,
-            url_query: None
+            url_query: None,
+            trailers: Headers::new()
         }
     }
 
@@ -79,6 +81,24 @@ impl RequestBuilder {
         self
     }
 
+    /// Send body with chunked transfer encoding
+    pub fn chunked(mut self) -> Self {
+        self.headers.put("Transfer-Encoding", "chunked".to_string());
+        self
+    }
+
+    /// Set body streamed from the source, it is sent chunked with trailers of the source
+    pub fn stream(mut self, source: impl BodySource + 'static) -> Self {
+        self.body = Some(Body::from_stream(source));
+        self
+    }
+
+    /// Set trailer header (sent only with chunked body)
+    pub fn trailer(mut self, name: impl ToString, value: impl ToString) -> Self {
+        self.trailers.add(name, value.to_string());
+        self
+    }
+
     /// Set body
     pub fn body(mut self, body: Body) -> Self {
         self.body = Some(body);
@@ -124,18 +144,23 @@ impl RequestBuilder {
     }
 
     /// Build request
-    pub fn build(self) -> Result<HttpRequest, HttpError> {
+    pub fn build(mut self) -> Result<HttpRequest, HttpError> {
         let mut url = self.url.to_url()?;
         if let Some(query) = self.url_query {
             url.query = query;
         }
+        let body = self.body.unwrap_or_default();
+        put_stream_framing(&mut self.headers, &body);
 
         Ok(HttpRequest { 
             url, 
             method: self.method,
+            version: "HTTP/1.1".to_string(),
             addr: None, 
+            local_addr: None,
             headers: self.headers, 
-            body: self.body.unwrap_or(Body::default())
+            body,
+            trailers: self.trailers
         })
     }
 }
@@ -0,0 +1,46 @@
+use std::{collections::HashMap, net::SocketAddr, sync::Arc};
+
+use async_trait::async_trait;
+use tokio::net::lookup_host;
+
+use crate::error::HttpError;
+
+/// Resolves host names to addresses for [`HttpClient`](super::HttpClient)
+#[async_trait]
+pub trait Resolver: Send + Sync {
+    /// Resolve host name to addresses with the port
+    async fn resolve(&self, host: &str, port: u16) -> Result<Vec<SocketAddr>, HttpError>;
+}
+
+/// Resolver that uses system resolver
+#[derive(Clone, Debug, Default)]
+pub struct SystemResolver;
+
+#[async_trait]
+impl Resolver for SystemResolver {
+    async fn resolve(&self, host: &str, port: u16) -> Result<Vec<SocketAddr>, HttpError> {
+        let addrs: Vec<SocketAddr> = lookup_host((host, port)).await
+            .map_err(|_| HttpError::ConnectError)?
+            .collect();
+        if addrs.is_empty() {
+            return Err(HttpError::ConnectError);
+        }
+        Ok(addrs)
+    }
+}
+
+/// Resolver with static addresses for some hosts, other hosts are resolved by inner resolver
+pub(super) struct OverrideResolver {
+    pub(super) overrides: HashMap<String, Vec<SocketAddr>>,
+    pub(super) inner: Arc<dyn Resolver>
+}
+
+#[async_trait]
+impl Resolver for OverrideResolver {
+    async fn resolve(&self, host: &str, port: u16) -> Result<Vec<SocketAddr>, HttpError> {
+        match self.overrides.get(&host.to_lowercase()) {
+            Some(addrs) => Ok(addrs.clone()),
+            None => self.inner.resolve(host, port).await
+        }
+    }
+}
@@ -0,0 +1,386 @@
+use std::{collections::HashMap, path::Path, pin::Pin, sync::{Arc, Mutex, OnceLock}};
+
+use base64::{prelude::BASE64_STANDARD, Engine};
+use openssl::{
+    hash::MessageDigest,
+    pkey::{PKey, Private},
+    ex_data::Index,
+    ssl::{Ssl, SslConnector, SslMethod, SslRef, SslSession, SslSessionCacheMode, SslVerifyMode, SslVersion},
+    x509::{store::X509StoreBuilder, X509Ref, X509}
+};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_openssl::SslStream;
+
+use crate::error::HttpError;
+
+/// Ssl settings of [`HttpClient`](super::HttpClient)
+#[derive(Clone, Debug)]
+pub struct TlsConfig {
+    /// Verify ssl certificates of the sites
+    pub verify: bool,
+    /// Trust system root certificates
+    pub system_roots: bool,
+    /// Additional trusted root certificates
+    pub root_certificates: Vec<X509>,
+    /// Client certificate for mutual tls
+    pub identity: Option<Identity>,
+    /// Pinned certificates by lowercase host name, one of them has to match
+    pub pins: HashMap<String, Vec<CertificatePin>>,
+    /// Minimal tls version, default of openssl if not set
+    pub min_version: Option<TlsVersion>,
+    /// Maximal tls version, default of openssl if not set
+    pub max_version: Option<TlsVersion>,
+    /// Cipher list for tls 1.2 and older, in openssl format (like `ECDHE+AESGCM:!aNULL`)
+    pub ciphers: Option<String>,
+    /// Cipher suites for tls 1.3, in openssl format (like `TLS_AES_256_GCM_SHA384`)
+    pub ciphersuites: Option<String>,
+    /// Keep tls sessions per host to resume them in the next connections
+    pub session_cache: bool
+}
+
+/// Tls protocol version
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TlsVersion {
+    Tls1_0,
+    Tls1_1,
+    Tls1_2,
+    Tls1_3
+}
+
+impl TlsVersion {
+    fn from_ssl(version: SslVersion) -> Option<Self> {
+        match version {
+            SslVersion::TLS1 => Some(TlsVersion::Tls1_0),
+            SslVersion::TLS1_1 => Some(TlsVersion::Tls1_1),
+            SslVersion::TLS1_2 => Some(TlsVersion::Tls1_2),
+            SslVersion::TLS1_3 => Some(TlsVersion::Tls1_3),
+            _ => None
+        }
+    }
+}
+
+impl From<TlsVersion> for SslVersion {
+    fn from(version: TlsVersion) -> Self {
+        match version {
+            TlsVersion::Tls1_0 => SslVersion::TLS1,
+            TlsVersion::Tls1_1 => SslVersion::TLS1_1,
+            TlsVersion::Tls1_2 => SslVersion::TLS1_2,
+            TlsVersion::Tls1_3 => SslVersion::TLS1_3
+        }
+    }
+}
+
+impl TlsConfig {
+    /// Build connector with the settings, it is reused for all connections
+    pub fn connector(&self) -> Result<TlsConnector, HttpError> {
+        let mut builder = SslConnector::builder(SslMethod::tls()).map_err(|_| HttpError::SslError)?;
+
+        builder.set_verify(if self.verify { SslVerifyMode::PEER } else { SslVerifyMode::NONE });
+
+        builder.set_min_proto_version(self.min_version.map(SslVersion::from)).map_err(|_| HttpError::SslError)?;
+        builder.set_max_proto_version(self.max_version.map(SslVersion::from)).map_err(|_| HttpError::SslError)?;
+        if let Some(ciphers) = &self.ciphers {
+            builder.set_cipher_list(ciphers).map_err(|_| HttpError::SslError)?;
+        }
+        if let Some(ciphersuites) = &self.ciphersuites {
+            builder.set_ciphersuites(ciphersuites).map_err(|_| HttpError::SslError)?;
+        }
+
+        if !self.system_roots {
+            builder.set_cert_store(X509StoreBuilder::new().map_err(|_| HttpError::SslError)?.build());
+        }
+        for cert in &self.root_certificates {
+            builder.cert_store_mut().add_cert(cert.clone()).map_err(|_| HttpError::SslError)?;
+        }
+
+        if let Some(identity) = &self.identity {
+            builder.set_certificate(&identity.certificate).map_err(|_| HttpError::SslError)?;
+            for cert in &identity.chain {
+                builder.add_extra_chain_cert(cert.clone()).map_err(|_| HttpError::SslError)?;
+            }
+            builder.set_private_key(&identity.key).map_err(|_| HttpError::SslError)?;
+            builder.check_private_key().map_err(|_| HttpError::SslError)?;
+        }
+
+        builder.set_alpn_protos(b"\x08http/1.1").map_err(|_| HttpError::SslError)?;
+
+        let sessions = if self.session_cache {
+            let sessions = SessionCache::default();
+            let now_sessions = sessions.clone();
+            builder.set_session_cache_mode(SslSessionCacheMode::CLIENT);
+            builder.set_new_session_callback(move |ssl, session| {
+                if let Some(host) = host_index().and_then(|o| ssl.ex_data(o)) {
+                    now_sessions.put(host.clone(), session);
+                }
+            });
+            Some(sessions)
+        } else {
+            None
+        };
+
+        Ok(TlsConnector { connector: builder.build(), config: Arc::new(self.clone()), sessions })
+    }
+
+    /// Add pinned certificate for the host
+    pub fn pin(&mut self, host: &str, pin: CertificatePin) {
+        self.pins.entry(host.to_lowercase()).or_default().push(pin);
+    }
+
+    /// Check that the connection matches one of the host pins, if it has them
+    pub fn check_pins(&self, host: &str, ssl: &SslRef) -> Result<(), HttpError> {
+        let Some(pins) = self.pins.get(&host.to_lowercase()) else { return Ok(()); };
+        if pins.is_empty() {
+            return Ok(());
+        }
+
+        // only the verified chain counts, certificates sent by the server may be unrelated to its own
+        let leaf = ssl.peer_certificate();
+        let chain = ssl.verified_chain();
+        let matches = pins.iter().any(|pin| match pin {
+            CertificatePin::Certificate(_) => leaf.as_ref().is_some_and(|o| pin.matches(o)),
+            CertificatePin::Spki(_) => leaf.iter().map(|o| o.as_ref()).chain(chain.into_iter().flatten()).any(|o| pin.matches(o))
+        });
+
+        if matches { Ok(()) } else { Err(HttpError::CertificatePinError) }
+    }
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        TlsConfig {
+            verify: true,
+            system_roots: true,
+            root_certificates: Vec::new(),
+            identity: None,
+            pins: HashMap::new(),
+            min_version: None,
+            max_version: None,
+            ciphers: None,
+            ciphersuites: None,
+            session_cache: true
+        }
+    }
+}
+
+/// How many hosts to keep tls sessions for, the cache is cleared when it is full
+const MAX_SESSIONS: usize = 256;
+
+/// Last tls sessions by host name
+#[derive(Clone, Default)]
+struct SessionCache(Arc<Mutex<HashMap<String, SslSession>>>);
+
+impl SessionCache {
+    fn get(&self, host: &str) -> Option<SslSession> {
+        self.0.lock().ok()?.get(host).cloned()
+    }
+
+    fn put(&self, host: String, session: SslSession) {
+        let Ok(mut sessions) = self.0.lock() else { return; };
+        if sessions.len() >= MAX_SESSIONS && !sessions.contains_key(&host) {
+            sessions.clear();
+        }
+        sessions.insert(host, session);
+    }
+}
+
+/// Index of host name in ssl data, to know which host new session is for
+fn host_index() -> Option<Index<Ssl, String>> {
+    static INDEX: OnceLock<Option<Index<Ssl, String>>> = OnceLock::new();
+    *INDEX.get_or_init(|| Ssl::new_ex_index().ok())
+}
+
+/// Ssl connector built from [`TlsConfig`], with its tls session cache
+#[derive(Clone)]
+pub struct TlsConnector {
+    connector: SslConnector,
+    config: Arc<TlsConfig>,
+    sessions: Option<SessionCache>
+}
+
+impl TlsConnector {
+    /// Do tls handshake with the host over the stream, resuming its last session if there is one
+    ///
+    /// Certificate pins of the host are checked after the handshake
+    pub async fn connect<S: AsyncRead + AsyncWrite>(&self, domain: &str, stream: S) -> Result<Pin<Box<SslStream<S>>>, HttpError> {
+        let mut ssl = self.connector
+            .configure()
+            .map_err(|_| HttpError::SslError)?
+            .into_ssl(domain)
+            .map_err(|_| HttpError::SslError)?;
+
+        if let (Some(sessions), Some(index)) = (&self.sessions, host_index()) {
+            let host = domain.to_lowercase();
+            if let Some(session) = sessions.get(&host) {
+                // SAFETY: sessions in the cache are made by this connector context
+                unsafe { ssl.set_session(&session).map_err(|_| HttpError::SslError)?; }
+            }
+            ssl.set_ex_data(index, host);
+        }
+
+        let mut wrapper = Box::pin(SslStream::new(ssl, stream).map_err(|_| HttpError::SslError)?);
+        wrapper.as_mut().connect().await.map_err(|_| HttpError::SslError)?;
+        self.config.check_pins(domain, wrapper.ssl())?;
+
+        Ok(wrapper)
+    }
+
+    /// Get settings the connector is built with
+    pub fn config(&self) -> &TlsConfig {
+        &self.config
+    }
+}
+
+/// Details of tls connection the response is received over
+#[derive(Clone, Debug)]
+pub struct TlsInfo {
+    /// Negotiated protocol version, `None` if it is not tls 1.0 - 1.3
+    pub version: Option<TlsVersion>,
+    /// Negotiated cipher, in openssl format
+    pub cipher: Option<String>,
+    /// Protocol selected by the server with ALPN
+    pub alpn: Option<String>,
+    /// Certificates sent by the server, leaf first
+    pub peer_certificates: Vec<X509>,
+    /// Session was resumed from the cache
+    pub session_reused: bool
+}
+
+impl TlsInfo {
+    /// Get details of the established connection
+    pub fn from_ssl(ssl: &SslRef) -> Self {
+        let peer_certificates = match ssl.peer_cert_chain() {
+            Some(chain) => chain.iter().map(|o| o.to_owned()).collect(),
+            None => ssl.peer_certificate().into_iter().collect()
+        };
+
+        TlsInfo {
+            version: ssl.version2().and_then(TlsVersion::from_ssl),
+            cipher: ssl.current_cipher().map(|o| o.name().to_string()),
+            alpn: ssl.selected_alpn_protocol().map(|o| String::from_utf8_lossy(o).to_string()),
+            peer_certificates,
+            session_reused: ssl.session_reused()
+        }
+    }
+
+    /// Get leaf certificate of the server
+    pub fn peer_certificate(&self) -> Option<&X509> {
+        self.peer_certificates.first()
+    }
+
+    /// Get server certificates in der
+    pub fn peer_certificates_der(&self) -> Vec<Vec<u8>> {
+        self.peer_certificates.iter().filter_map(|o| o.to_der().ok()).collect()
+    }
+
+    /// Get server certificates in one pem
+    pub fn peer_certificates_pem(&self) -> String {
+        self.peer_certificates.iter()
+            .filter_map(|o| o.to_pem().ok())
+            .map(|o| String::from_utf8_lossy(&o).to_string())
+            .collect()
+    }
+}
+
+/// Client certificate with its chain and private key
+#[derive(Clone, Debug)]
+pub struct Identity {
+    pub certificate: X509,
+    /// Intermediate certificates sent after the client certificate
+    pub chain: Vec<X509>,
+    pub key: PKey<Private>
+}
+
+impl Identity {
+    /// Read identity from pem, first certificate is the client one, others are the chain
+    pub fn from_pem(certificates: &[u8], key: &[u8]) -> Result<Self, HttpError> {
+        let mut chain = certificates_from_pem(certificates)?;
+        let certificate = chain.remove(0);
+        let key = PKey::private_key_from_pem(key).map_err(|_| HttpError::SslError)?;
+        Ok(Identity { certificate, chain, key })
+    }
+
+    /// Read identity from pem files
+    pub fn from_files(certificates: impl AsRef<Path>, key: impl AsRef<Path>) -> Result<Self, HttpError> {
+        Self::from_pem(&read_file(certificates)?, &read_file(key)?)
+    }
+}
+
+/// Pinned certificate, by sha256 hash
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CertificatePin {
+    /// Hash of the public key (SubjectPublicKeyInfo) of the leaf or any certificate in the verified chain
+    Spki(Vec<u8>),
+    /// Fingerprint of the leaf certificate
+    Certificate(Vec<u8>)
+}
+
+impl CertificatePin {
+    /// Parse base64 public key hash, like `sha256/AbC...=` or `AbC...=`
+    pub fn spki(hash: &str) -> Result<Self, HttpError> {
+        let hash = hash.trim();
+        let hash = hash.strip_prefix("sha256/").unwrap_or(hash);
+        let hash = BASE64_STANDARD.decode(hash).map_err(|_| HttpError::SslError)?;
+        if hash.len() != 32 {
+            return Err(HttpError::SslError);
+        }
+        Ok(CertificatePin::Spki(hash))
+    }
+
+    /// Parse hex certificate fingerprint, bytes can be separated by colons
+    pub fn certificate(fingerprint: &str) -> Result<Self, HttpError> {
+        let hex: String = fingerprint.trim().chars().filter(|o| *o != ':').collect();
+        if hex.len() != 64 {
+            return Err(HttpError::SslError);
+        }
+        let hash = (0..hex.len()).step_by(2)
+            .map(|i| hex.get(i..i + 2).and_then(|o| u8::from_str_radix(o, 16).ok()))
+            .collect::<Option<Vec<u8>>>()
+            .ok_or(HttpError::SslError)?;
+        Ok(CertificatePin::Certificate(hash))
+    }
+
+    /// Make pin of the certificate public key
+    pub fn spki_of(cert: &X509Ref) -> Result<Self, HttpError> {
+        Ok(CertificatePin::Spki(spki_hash(cert).ok_or(HttpError::SslError)?))
+    }
+
+    /// Make pin of the certificate fingerprint
+    pub fn certificate_of(cert: &X509Ref) -> Result<Self, HttpError> {
+        Ok(CertificatePin::Certificate(certificate_hash(cert).ok_or(HttpError::SslError)?))
+    }
+
+    /// Check if the certificate matches the pin
+    pub fn matches(&self, cert: &X509Ref) -> bool {
+        match self {
+            CertificatePin::Spki(hash) => spki_hash(cert).is_some_and(|o| &o == hash),
+            CertificatePin::Certificate(hash) => certificate_hash(cert).is_some_and(|o| &o == hash)
+        }
+    }
+}
+
+fn spki_hash(cert: &X509Ref) -> Option<Vec<u8>> {
+    let der = cert.public_key().ok()?.public_key_to_der().ok()?;
+    Some(openssl::sha::sha256(&der).to_vec())
+}
+
+fn certificate_hash(cert: &X509Ref) -> Option<Vec<u8>> {
+    Some(cert.digest(MessageDigest::sha256()).ok()?.to_vec())
+}
+
+/// Read all certificates from pem, there has to be at least one
+pub fn certificates_from_pem(pem: &[u8]) -> Result<Vec<X509>, HttpError> {
+    let certs = X509::stack_from_pem(pem).map_err(|_| HttpError::SslError)?;
+    if certs.is_empty() {
+        return Err(HttpError::SslError);
+    }
+    Ok(certs)
+}
+
+/// Read all certificates from pem file
+pub fn certificates_from_file(path: impl AsRef<Path>) -> Result<Vec<X509>, HttpError> {
+    certificates_from_pem(&read_file(path)?)
+}
+
+fn read_file(path: impl AsRef<Path>) -> Result<Vec<u8>, HttpError> {
+    std::fs::read(path).map_err(|_| HttpError::SslError)
+}
@@ -0,0 +1,524 @@
+//! Sans-IO http/1.1 codec
+//!
+//! Parses and serializes http messages from and into byte buffers,
+//! without touching any stream. Async `recv` and `send` methods are built on top of it.
+
+use super::{
+    MAX_HEAD_SIZE,
+    body::Body,
+    error::HttpError,
+    headers::Headers,
+    request::HttpRequest,
+    response::HttpResponse
+};
+
+/// Request line and headers of http request
+#[derive(Debug, Clone)]
+pub struct RequestHead {
+    pub method: String,
+    pub target: String,
+    pub version: String,
+    pub headers: Headers
+}
+
+/// Status line and headers of http response
+#[derive(Debug, Clone)]
+pub struct ResponseHead {
+    pub version: String,
+    pub status_code: String,
+    pub headers: Headers
+}
+
+/// Find end of message head (the byte after empty line)
+pub fn find_head_end(buf: &[u8]) -> Option<usize> {
+    let mut start = 0;
+    while let Some(pos) = buf[start..].iter().position(|o| *o == b'\n') {
+        let line = &buf[start..start + pos];
+        start += pos + 1;
+        if line.is_empty() || line == b"\r" {
+            return Some(start);
+        }
+    }
+    None
+}
+
+fn split_lines(buf: &[u8]) -> Result<Vec<&str>, HttpError> {
+    let text = std::str::from_utf8(buf).map_err(|_| HttpError::ReadLineUnknown)?;
+    Ok(text.split('\n')
+        .map(|o| o.strip_suffix('\r').unwrap_or(o))
+        .collect())
+}
+
+fn parse_header_lines(lines: &[&str]) -> Result<Headers, HttpError> {
+    let mut headers = Headers::new();
+    for line in lines {
+        if line.is_empty() { break }
+        let (key, value) = line.split_once(':').ok_or(HttpError::InvalidHeaders)?;
+        if key.is_empty() || key.ends_with(|c: char| c.is_whitespace()) {
+            return Err(HttpError::InvalidHeaders);
+        }
+        headers.add(key, value.trim().to_string());
+    }
+    Ok(headers)
+}
+
+/// Parse headers block ending with empty line
+///
+/// Returns `None` if buffer doesn't contain the whole block yet,
+/// otherwise headers and count of consumed bytes
+pub fn parse_headers(buf: &[u8]) -> Result<Option<(Headers, usize)>, HttpError> {
+    let Some(end) = find_head_end(buf) else { return Ok(None) };
+    let headers = parse_header_lines(&split_lines(&buf[..end])?)?;
+    Ok(Some((headers, end)))
+}
+
+/// Parse request line and headers
+///
+/// Returns `None` if buffer doesn't contain the whole head yet,
+/// otherwise head and count of consumed bytes
+pub fn parse_request_head(buf: &[u8]) -> Result<Option<(RequestHead, usize)>, HttpError> {
+    let Some(end) = find_head_end(buf) else { return Ok(None) };
+    let lines = split_lines(&buf[..end])?;
+
+    let mut status = lines[0].splitn(3, ' ');
+    let method = status.next().filter(|o| !o.is_empty()).ok_or(HttpError::InvalidStatus)?;
+    let target = status.next().filter(|o| !o.is_empty()).ok_or(HttpError::InvalidStatus)?;
+    let version = status.next().unwrap_or("HTTP/1.1");
+
+    Ok(Some((RequestHead {
+        method: method.to_string(),
+        target: target.to_string(),
+        version: version.to_string(),
+        headers: parse_header_lines(&lines[1..])?
+    }, end)))
+}
+
+/// Parse status line and headers
+///
+/// Returns `None` if buffer doesn't contain the whole head yet,
+/// otherwise head and count of consumed bytes
+pub fn parse_response_head(buf: &[u8]) -> Result<Option<(ResponseHead, usize)>, HttpError> {
+    let Some(end) = find_head_end(buf) else { return Ok(None) };
+    let lines = split_lines(&buf[..end])?;
+
+    let (version, status_code) = lines[0].split_once(' ').ok_or(HttpError::InvalidStatus)?;
+
+    Ok(Some((ResponseHead {
+        version: version.to_string(),
+        status_code: status_code.to_string(),
+        headers: parse_header_lines(&lines[1..])?
+    }, end)))
+}
+
+/// How the message body is delimited
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BodyFraming {
+    Empty,
+    Length(usize),
+    Chunked,
+    Close
+}
+
+fn parse_content_length(headers: &Headers) -> Result<Option<usize>, HttpError> {
+    let mut length = None;
+    for value in headers.get("content-length").iter().flat_map(|o| o.split(',')) {
+        let value = value.trim();
+        if value.is_empty() || !value.bytes().all(|c| c.is_ascii_digit()) {
+            return Err(HttpError::InvalidContentSize);
+        }
+        let value: usize = value.parse().map_err(|_| HttpError::InvalidContentSize)?;
+        if length.is_some_and(|o| o != value) {
+            return Err(HttpError::ConflictingFraming);
+        }
+        length = Some(value);
+    }
+    Ok(length)
+}
+
+fn parse_transfer_encoding(headers: &Headers) -> Option<Vec<String>> {
+    let values = headers.get("transfer-encoding");
+    if values.is_empty() {
+        return None;
+    }
+    Some(values.iter()
+        .flat_map(|o| o.split(','))
+        .map(|o| o.trim().to_lowercase())
+        .filter(|o| !o.is_empty())
+        .collect())
+}
+
+impl BodyFraming {
+    /// Get body framing from message headers
+    ///
+    /// `Transfer-Encoding` takes precedence over `Content-Length`,
+    /// a non-chunked transfer coding means that the body lasts until the connection is closed. \
+    /// Without both headers body is empty, as for requests
+    pub fn from_headers(headers: &Headers) -> Result<BodyFraming, HttpError> {
+        if let Some(codings) = parse_transfer_encoding(headers) {
+            if codings.last().is_some_and(|o| o == "chunked") {
+                Ok(BodyFraming::Chunked)
+            } else {
+                Ok(BodyFraming::Close)
+            }
+        } else if let Some(content_size) = parse_content_length(headers)? {
+            Ok(BodyFraming::Length(content_size))
+        } else {
+            Ok(BodyFraming::Empty)
+        }
+    }
+
+    /// Get body framing from request headers
+    ///
+    /// Unlike [`from_headers`](BodyFraming::from_headers), rejects requests
+    /// with both `Transfer-Encoding` and `Content-Length` or without final chunked coding,
+    /// because their length can be read differently by proxies (request smuggling)
+    pub fn from_request_headers(headers: &Headers) -> Result<BodyFraming, HttpError> {
+        if let Some(codings) = parse_transfer_encoding(headers) {
+            if headers.contains("content-length") {
+                return Err(HttpError::ConflictingFraming);
+            }
+            if codings.last().is_none_or(|o| o != "chunked") || 
+                    codings.iter().filter(|o| *o == "chunked").count() > 1 {
+                return Err(HttpError::InvalidTransferEncoding);
+            }
+        }
+        Self::from_headers(headers)
+    }
+
+    /// Get body framing from response head
+    ///
+    /// `method` is the method of the request, responses to `HEAD` and `CONNECT` (2xx),
+    /// and responses with 1xx, 204 and 304 status codes have no body regardless of headers. \
+    /// Other responses without `Content-Length` and `Transfer-Encoding` last until the connection is closed (RFC 9112 6.3)
+    pub fn from_response_head(status_code: &str, method: Option<&str>, headers: &Headers) -> Result<BodyFraming, HttpError> {
+        let code = parse_status_code(status_code).ok_or(HttpError::InvalidStatus)?;
+        if !has_response_body(code, method) {
+            return Ok(BodyFraming::Empty);
+        }
+        match Self::from_headers(headers)? {
+            BodyFraming::Empty => Ok(BodyFraming::Close),
+            framing => Ok(framing)
+        }
+    }
+}
+
+/// Parse numeric status code from status (like `200 OK`)
+pub fn parse_status_code(status_code: &str) -> Option<u16> {
+    status_code.split(' ').next()?.parse().ok()
+}
+
+/// Can response with this status code to request with this method have a body
+pub fn has_response_body(code: u16, method: Option<&str>) -> bool {
+    match method {
+        Some("HEAD") => false,
+        Some("CONNECT") if (200..300).contains(&code) => false,
+        _ => !((100..200).contains(&code) || code == 204 || code == 304),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DecodeState {
+    Length(usize),
+    UntilClose,
+    ChunkSize,
+    ChunkData(usize),
+    ChunkEnd,
+    Trailer,
+    Done
+}
+
+fn parse_chunk_size(line: &[u8]) -> Result<usize, HttpError> {
+    let line = std::str::from_utf8(line).map_err(|_| HttpError::InvalidContent)?;
+    let size = match line.split_once(';') {
+        Some((size, _extensions)) => size.trim_end_matches([' ', '\t']),
+        None => line,
+    };
+    if size.is_empty() || !size.bytes().all(|c| c.is_ascii_hexdigit()) {
+        return Err(HttpError::InvalidContent);
+    }
+    usize::from_str_radix(size, 16).map_err(|_| HttpError::InvalidContent)
+}
+
+/// Incremental body decoder
+///
+/// Feed it with bytes following the message head until [`is_done`](BodyDecoder::is_done)
+#[derive(Debug, Clone)]
+pub struct BodyDecoder {
+    state: DecodeState,
+    line: Vec<u8>,
+    data: Vec<u8>,
+    trailers: Headers,
+    trailers_size: usize
+}
+
+impl BodyDecoder {
+    pub fn new(framing: BodyFraming) -> Self {
+        BodyDecoder {
+            state: match framing {
+                BodyFraming::Empty | BodyFraming::Length(0) => DecodeState::Done,
+                BodyFraming::Length(size) => DecodeState::Length(size),
+                BodyFraming::Chunked => DecodeState::ChunkSize,
+                BodyFraming::Close => DecodeState::UntilClose,
+            },
+            line: Vec::new(),
+            data: Vec::new(),
+            trailers: Headers::new(),
+            trailers_size: 0
+        }
+    }
+
+    /// Is the whole body decoded
+    pub fn is_done(&self) -> bool {
+        self.state == DecodeState::Done
+    }
+
+    /// Count of bytes that can be read from the stream without touching the next message
+    pub fn hint(&self) -> usize {
+        match self.state {
+            DecodeState::Length(size) | DecodeState::ChunkData(size) => size,
+            DecodeState::UntilClose => usize::MAX,
+            DecodeState::ChunkEnd => 2 - self.line.len().min(1),
+            DecodeState::ChunkSize | DecodeState::Trailer => 1,
+            DecodeState::Done => 0,
+        }
+    }
+
+    /// Decode part of the body
+    ///
+    /// Returns count of consumed bytes, it is less than `buf` length only when body is done
+    pub fn decode(&mut self, buf: &[u8]) -> Result<usize, HttpError> {
+        let mut pos = 0;
+
+        while pos < buf.len() {
+            match self.state {
+                DecodeState::Length(size) | DecodeState::ChunkData(size) => {
+                    let count = size.min(buf.len() - pos);
+                    self.data.extend_from_slice(&buf[pos..pos + count]);
+                    pos += count;
+                    self.state = match (self.state, size - count) {
+                        (DecodeState::Length(_), 0) => DecodeState::Done,
+                        (DecodeState::Length(_), left) => DecodeState::Length(left),
+                        (_, 0) => DecodeState::ChunkEnd,
+                        (_, left) => DecodeState::ChunkData(left),
+                    };
+                }
+                DecodeState::UntilClose => {
+                    self.data.extend_from_slice(&buf[pos..]);
+                    pos = buf.len();
+                }
+                DecodeState::ChunkSize => {
+                    let Some(line) = self.take_line(buf, &mut pos)? else { continue };
+                    let size = parse_chunk_size(&line)?;
+                    self.state = if size == 0 {
+                        DecodeState::Trailer
+                    } else {
+                        DecodeState::ChunkData(size)
+                    };
+                }
+                DecodeState::ChunkEnd => {
+                    let Some(line) = self.take_line(buf, &mut pos)? else { continue };
+                    if !line.is_empty() {
+                        return Err(HttpError::InvalidContent);
+                    }
+                    self.state = DecodeState::ChunkSize;
+                }
+                DecodeState::Trailer => {
+                    let Some(line) = self.take_line(buf, &mut pos)? else { continue };
+                    if line.is_empty() {
+                        self.state = DecodeState::Done;
+                        continue;
+                    }
+                    self.trailers_size += line.len() + 2;
+                    if self.trailers_size > MAX_HEAD_SIZE {
+                        return Err(HttpError::HeadTooLarge);
+                    }
+                    let line = String::from_utf8(line).map_err(|_| HttpError::InvalidHeaders)?;
+                    let trailers = parse_header_lines(&[&line])?;
+                    for (key, value) in trailers.entries() {
+                        self.trailers.add(key, value);
+                    }
+                }
+                DecodeState::Done => break,
+            }
+        }
+
+        Ok(pos)
+    }
+
+    /// Signal that the stream is closed
+    ///
+    /// Completes the body delimited by connection close, fails if the body is truncated
+    pub fn finish(&mut self) -> Result<(), HttpError> {
+        match self.state {
+            DecodeState::UntilClose | DecodeState::Done => {
+                self.state = DecodeState::Done;
+                Ok(())
+            }
+            _ => Err(HttpError::InvalidContent),
+        }
+    }
+
+    /// Get trailer headers received after the last chunk
+    pub fn trailers(&self) -> &Headers {
+        &self.trailers
+    }
+
+    /// Take decoded body
+    pub fn into_body(self) -> Body {
+        Body::new(self.data)
+    }
+
+    /// Take decoded body and trailer headers
+    pub fn into_parts(self) -> (Body, Headers) {
+        (Body::new(self.data), self.trailers)
+    }
+
+    /// Take line ending in `buf`, or keep its start until more data comes
+    ///
+    /// Lines longer than the head size limit are rejected
+    fn take_line(&mut self, buf: &[u8], pos: &mut usize) -> Result<Option<Vec<u8>>, HttpError> {
+        let end = buf[*pos..].iter().position(|o| *o == b'\n');
+        let part = &buf[*pos..*pos + end.unwrap_or(buf.len() - *pos)];
+        if self.line.len() + part.len() > MAX_HEAD_SIZE {
+            return Err(HttpError::HeadTooLarge);
+        }
+        self.line.extend_from_slice(part);
+
+        match end {
+            Some(index) => {
+                *pos += index + 1;
+                let mut line = std::mem::take(&mut self.line);
+                if line.last() == Some(&b'\r') {
+                    line.pop();
+                }
+                Ok(Some(line))
+            }
+            None => {
+                *pos = buf.len();
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// Serialize headers
+pub fn encode_headers(headers: &Headers, buf: &mut Vec<u8>) {
+    for (k, v) in headers.entries() {
+        buf.extend_from_slice(k.as_bytes());
+        buf.extend_from_slice(b": ");
+        buf.extend_from_slice(v.as_bytes());
+        buf.extend_from_slice(b"\r\n");
+    }
+}
+
+/// Serialize request line and headers
+pub fn encode_request_head(method: &str, target: &str, headers: &Headers, buf: &mut Vec<u8>) {
+    buf.extend_from_slice(method.as_bytes());
+    buf.push(b' ');
+    buf.extend_from_slice(target.as_bytes());
+    buf.extend_from_slice(b" HTTP/1.1\r\n");
+    encode_headers(headers, buf);
+    buf.extend_from_slice(b"\r\n");
+}
+
+/// Serialize status line and headers
+pub fn encode_response_head(status_code: &str, headers: &Headers, buf: &mut Vec<u8>) {
+    buf.extend_from_slice(b"HTTP/1.1 ");
+    buf.extend_from_slice(status_code.as_bytes());
+    buf.extend_from_slice(b"\r\n");
+    encode_headers(headers, buf);
+    buf.extend_from_slice(b"\r\n");
+}
+
+/// Serialize one chunk of chunked body, empty data is skipped
+pub fn encode_chunk(data: &[u8], buf: &mut Vec<u8>) {
+    if data.is_empty() {
+        return;
+    }
+    buf.extend_from_slice(format!("{:X}\r\n", data.len()).as_bytes());
+    buf.extend_from_slice(data);
+    buf.extend_from_slice(b"\r\n");
+}
+
+/// Serialize last chunk of chunked body with trailer headers
+pub fn encode_last_chunk(trailers: &Headers, buf: &mut Vec<u8>) {
+    buf.extend_from_slice(b"0\r\n");
+    encode_headers(trailers, buf);
+    buf.extend_from_slice(b"\r\n");
+}
+
+/// Is message with these headers sent with chunked transfer encoding
+pub fn is_chunked(headers: &Headers) -> bool {
+    parse_transfer_encoding(headers).is_some_and(|o| o.last().is_some_and(|o| o == "chunked"))
+}
+
+fn chunked_headers(headers: &Headers, trailers: &Headers) -> Headers {
+    let mut headers = headers.clone();
+    headers.remove("content-length");
+    if !trailers.is_empty() {
+        headers.put_default("Trailer", trailers.keys().join(", "));
+    }
+    headers
+}
+
+/// Set `Transfer-Encoding: chunked` for streamed body without `Content-Length`
+pub fn put_stream_framing(headers: &mut Headers, body: &Body) {
+    if body.is_stream() && headers.get("content-length").is_empty() && !is_chunked(headers) {
+        headers.put("Transfer-Encoding", "chunked".to_string());
+    }
+}
+
+/// Streamed bodies are not serialized, they are written after the head
+fn encode_body(headers: &Headers, body: &Body, trailers: &Headers, buf: &mut Vec<u8>) {
+    if body.is_stream() {
+        return;
+    }
+    if is_chunked(headers) {
+        encode_chunk(&body.data, buf);
+        encode_last_chunk(trailers, buf);
+    } else {
+        buf.extend_from_slice(&body.data);
+    }
+}
+
+/// Serialize the whole http request, only the head if body is streamed
+///
+/// Body is sent chunked with trailers if `Transfer-Encoding: chunked` is set
+pub fn encode_request(request: &HttpRequest, buf: &mut Vec<u8>) {
+    let mut url = request.url.clone();
+    url.root = None;
+    encode_request_with_target(request, &url.to_string(), buf);
+}
+
+/// Serialize the whole http request with absolute url as target, for http proxies
+pub fn encode_request_absolute(request: &HttpRequest, buf: &mut Vec<u8>) {
+    encode_request_with_target(request, &request.url.to_string(), buf);
+}
+
+fn encode_request_with_target(request: &HttpRequest, target: &str, buf: &mut Vec<u8>) {
+    if is_chunked(&request.headers) {
+        let headers = chunked_headers(&request.headers, &request.trailers);
+        encode_request_head(&request.method, target, &headers, buf);
+    } else {
+        encode_request_head(&request.method, target, &request.headers, buf);
+    }
+    encode_body(&request.headers, &request.body, &request.trailers, buf);
+}
+
+/// Serialize http response status line and headers, without body (for `HEAD` requests)
+pub fn encode_response_without_body(response: &HttpResponse, buf: &mut Vec<u8>) {
+    if is_chunked(&response.headers) {
+        let headers = chunked_headers(&response.headers, &response.trailers);
+        encode_response_head(&response.status_code, &headers, buf);
+    } else {
+        encode_response_head(&response.status_code, &response.headers, buf);
+    }
+}
+
+/// Serialize the whole http response, only the head if body is streamed
+///
+/// Body is sent chunked with trailers if `Transfer-Encoding: chunked` is set
+pub fn encode_response(response: &HttpResponse, buf: &mut Vec<u8>) {
+    encode_response_without_body(response, buf);
+    encode_body(&response.headers, &response.body, &response.trailers, buf);
+}
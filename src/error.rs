@@ -6,9 +6,12 @@ pub enum HttpError {
     ReadLineEof,
     ReadLineUnknown,
     InvalidHeaders,
+    HeadTooLarge,
     InvalidQuery,
     InvalidContentSize,
     InvalidContent,
+    ConflictingFraming,
+    InvalidTransferEncoding,
     JsonParseError,
     WriteHeadError,
     WriteBodyError,
@@ -16,8 +19,11 @@ pub enum HttpError {
     RequestError,
     UrlError,
     ConnectError,
+    ProxyConnectError,
+    AcceptError,
     ShutdownError,
     SslError,
+    CertificatePinError,
     UnknownScheme,
     UrlNeedsRootError
 }
@@ -6,7 +6,7 @@ use std::{
 use async_trait::async_trait;
 use tokio::io::{AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
-use super::{error::HttpError, read_line_crlf, Sendable};
+use super::{codec::{encode_headers, parse_headers}, error::HttpError, read_head, Sendable};
 
 /// Http headers
 #[derive(Clone, Debug)]
@@ -99,21 +99,17 @@ impl Headers {
         return self.entries.len();
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
     pub fn clear(&mut self) {
         self.entries.clear();
     }
 
     pub async fn recv(stream: &mut (impl AsyncReadExt + Unpin)) -> Result<Headers, HttpError> {
-        let mut headers = Headers::new();
-
-        loop {
-            let text = read_line_crlf(stream).await.map_err(|_| HttpError::InvalidHeaders)?;
-            if text.len() == 0 { break }
-
-            let (key, value) = text.split_once(": ").ok_or(HttpError::InvalidHeaders)?;
-            headers.add(key, value.to_string());
-        }
-
+        let head = read_head(stream, false).await.map_err(|_| HttpError::InvalidHeaders)?;
+        let (headers, _) = parse_headers(&head)?.ok_or(HttpError::InvalidHeaders)?;
         Ok(headers)
     }
 }
@@ -130,14 +126,9 @@ impl Sendable for Headers {
         &self,
         stream: &mut (dyn AsyncWrite + Unpin + Send + Sync),
     ) -> Result<(), HttpError> {
-        let mut head = String::new();
-        for (k, v) in self.entries() {
-            head.push_str(&k);
-            head.push_str(": ");
-            head.push_str(&v);
-            head.push_str("\r\n");
-        }
-        stream.write_all(head.as_bytes()).await.map_err(|_| HttpError::WriteHeadError)
+        let mut head = Vec::new();
+        encode_headers(self, &mut head);
+        stream.write_all(&head).await.map_err(|_| HttpError::WriteHeadError)
     }
     fn as_box(self) -> Box<dyn Sendable> {
         Box::new(self)
@@ -5,6 +5,7 @@ pub mod response;
 pub mod body;
 pub mod server;
 pub mod client;
+pub mod codec;
 
 pub mod prelude {
     pub use super::error::*;
@@ -15,13 +16,16 @@ pub mod prelude {
     pub use super::body::*;
     pub use super::server::*;
     pub use super::server::handler::*;
+    pub use super::server::listener::*;
     pub use super::server::starter::*;
     pub use super::*;
 }
 
 use error::HttpError;
+use response::HttpResponse;
 use rand::Rng;
-use tokio::{io::{AsyncReadExt, AsyncWrite}, net::TcpStream};
+use server::listener::Transport;
+use tokio::io::{AsyncReadExt, AsyncWrite};
 use tokio_io_timeout::TimeoutStream;
 use async_trait::async_trait;
 
@@ -86,24 +90,34 @@ fn split_bytes(bytes: &[u8], sep: &[u8]) -> Vec<Vec<u8>> {
     }
 }
 
-async fn read_line(data: &mut (impl AsyncReadExt + Unpin)) -> Result<String, HttpError> {
-    let mut line = Vec::new();
+/// Max size of request or response head, and of trailers and chunk lines
+pub(crate) const MAX_HEAD_SIZE: usize = 64 * 1024;
+
+/// Read head ending with empty line
+///
+/// Empty lines before the head are skipped if `skip_empty` (RFC 9112 2.2),
+/// otherwise empty line is the whole head (empty trailers)
+async fn read_head(data: &mut (impl AsyncReadExt + Unpin), skip_empty: bool) -> Result<Vec<u8>, HttpError> {
+    let mut head = Vec::new();
+    let mut size = 0;
     loop {
-        let mut buffer = vec![0;1];
-        data.read_exact(&mut buffer).await.or(Err(HttpError::ReadLineEof))?;
-        let char = buffer[0];
-        line.push(char);
-        if char == 0x0a {
-            break;
+        let byte = data.read_u8().await.or(Err(HttpError::ReadLineEof))?;
+        size += 1;
+        if size > MAX_HEAD_SIZE {
+            return Err(HttpError::HeadTooLarge);
         }
-    }
-    String::from_utf8(line).or(Err(HttpError::ReadLineUnknown))
-}
 
-async fn read_line_crlf(data: &mut (impl AsyncReadExt + Unpin)) -> Result<String, HttpError> {
-    match read_line(data).await {
-        Ok(i) => Ok(i[..i.len() - 2].to_string()),
-        Err(e) => Err(e),
+        head.push(byte);
+        if head == b"\r\n" || head == b"\n" {
+            if skip_empty {
+                head.clear();
+                continue;
+            }
+            return Ok(head);
+        }
+        if head.ends_with(b"\n\n") || head.ends_with(b"\n\r\n") {
+            return Ok(head);
+        }
     }
 }
 
@@ -114,6 +128,13 @@ pub trait Sendable: Send + Sync {
         stream: &mut (dyn AsyncWrite + Unpin + Send + Sync),
     ) -> Result<(), HttpError>;
     fn as_box(self) -> Box<dyn Sendable>;
+
+    /// Get as http response, so the server can adjust it to the request (`HEAD` method, keep-alive)
+    ///
+    /// Other sendables are sent as is
+    fn as_response_mut(&mut self) -> Option<&mut HttpResponse> {
+        None
+    }
 }
 
-pub type Stream = TimeoutStream<TcpStream>;
\ No newline at end of file
+pub type Stream = TimeoutStream<Box<dyn Transport>>;
\ No newline at end of file
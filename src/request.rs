@@ -1,4 +1,4 @@
-use super::{body::{Body, Part}, client::RequestBuilder, gen_multipart_boundary, headers::Headers, read_line_crlf, HttpError, Sendable};
+use super::{body::{Body, Part}, client::RequestBuilder, codec::{encode_request, is_chunked, parse_request_head, put_stream_framing, BodyFraming}, gen_multipart_boundary, headers::Headers, read_head, HttpError, Sendable};
 
 use std::{
     collections::HashMap, fmt::{Debug, Display}, net::SocketAddr, str::FromStr
@@ -185,9 +185,13 @@ impl IntoRequest for HttpRequest {
 pub struct HttpRequest {
     pub url: URL,
     pub method: String,
+    pub version: String,
     pub addr: Option<SocketAddr>,
+    /// Local address of the server listener that accepted the request
+    pub local_addr: Option<SocketAddr>,
     pub headers: Headers,
-    pub body: Body
+    pub body: Body,
+    pub trailers: Headers
 }
 
 impl Display for HttpRequest {
@@ -197,47 +201,88 @@ impl Display for HttpRequest {
 }
 
 impl HttpRequest {
-    /// Create new http request
+    /// Create new http request, streamed body without `Content-Length` is sent chunked
     pub fn new(
         url: impl IntoURL,
         method: String,
-        headers: Headers,
+        mut headers: Headers,
         body: Body,
         addr: Option<SocketAddr>
     ) -> Result<Self, HttpError> {
+        put_stream_framing(&mut headers, &body);
         Ok(HttpRequest {
             url: url.to_url()?,
             method,
+            version: "HTTP/1.1".to_string(),
             headers,
             body,
-            addr
+            addr,
+            local_addr: None,
+            trailers: Headers::new()
         })
     }
 
     /// Read http request from stream
     pub async fn recv(stream: &mut (impl AsyncReadExt + Unpin), addr: &SocketAddr) -> Result<HttpRequest, HttpError> {
-        let status: Vec<String> = match read_line_crlf(stream).await {
-            Ok(i) => {
-                i.splitn(3, " ")
-                    .map(|s| s.to_string())
-                    .collect()
-            },
-            Err(e) => return Err(e),
-        };
+        let mut request = Self::recv_head(stream, addr).await?;
+        request.recv_body(stream).await?;
+        Ok(request)
+    }
 
-        let method = status[0].clone();
-        let page = status[1].clone();
+    /// Read http request line and headers from stream, body is left empty
+    pub async fn recv_head(stream: &mut (impl AsyncReadExt + Unpin), addr: &SocketAddr) -> Result<HttpRequest, HttpError> {
+        Self::recv_head_from(stream, Some(*addr)).await
+    }
 
-        let headers = Headers::recv(stream).await?;
-        let body = Body::recv(stream, &headers).await?;
+    /// Read http request line and headers from stream of client with maybe unknown address
+    pub(crate) async fn recv_head_from(stream: &mut (impl AsyncReadExt + Unpin), addr: Option<SocketAddr>) -> Result<HttpRequest, HttpError> {
+        let head = read_head(stream, true).await?;
+        let (head, _) = parse_request_head(&head)?.ok_or(HttpError::InvalidStatus)?;
 
-        HttpRequest::new(
-            page,
-            method, 
-            headers, 
-            body,
-            Some(addr.clone())
-        )
+        let mut request = HttpRequest::new(
+            head.target,
+            head.method, 
+            head.headers, 
+            Body::default(),
+            addr
+        )?;
+        request.version = head.version;
+        Ok(request)
+    }
+
+    /// Read http request body and trailers from stream
+    pub async fn recv_body(&mut self, stream: &mut (impl AsyncReadExt + Unpin)) -> Result<(), HttpError> {
+        let framing = BodyFraming::from_request_headers(&self.headers)?;
+        (self.body, self.trailers) = Body::recv_framed(stream, framing).await?;
+        Ok(())
+    }
+
+    /// Does client want to keep the connection open after response
+    ///
+    /// `Connection: close` closes it, HTTP/1.0 requests need `Connection: keep-alive`
+    pub fn keep_alive(&self) -> bool {
+        let connection: Vec<String> = self.headers.get("connection").iter()
+            .flat_map(|o| o.split(','))
+            .map(|o| o.trim().to_lowercase())
+            .collect();
+
+        if connection.iter().any(|o| o == "close") {
+            false
+        } else if self.version == "HTTP/1.0" {
+            connection.iter().any(|o| o == "keep-alive")
+        } else {
+            true
+        }
+    }
+
+    /// Can interim (1xx) responses be sent to client, HTTP/1.0 clients don't support them
+    pub fn accepts_interim(&self) -> bool {
+        self.version != "HTTP/1.0"
+    }
+
+    /// Is request expecting `100 Continue` before sending the body
+    pub fn expects_continue(&self) -> bool {
+        self.headers.get("expect").iter().any(|o| o.trim().eq_ignore_ascii_case("100-continue"))
     }
 
     /// Get multipart parts (requires Content-Type header)
@@ -270,23 +315,12 @@ impl Sendable for HttpRequest {
         &self,
         stream: &mut (dyn AsyncWrite + Unpin + Send + Sync),
     ) -> Result<(), HttpError> {
-        let mut url = self.url.clone();
-        url.root = None;
-
-        let mut head: String = String::new();
-        head.push_str(&self.method);
-        head.push_str(" ");
-        head.push_str(&url.to_string());
-        head.push_str(" HTTP/1.1");
-        head.push_str("\r\n");
-        stream.write_all(head.as_bytes()).await.map_err(|_| HttpError::WriteHeadError)?;
-
-        self.headers.send(stream).await?;
-
-        stream.write_all(b"\r\n").await.map_err(|_| HttpError::WriteBodyError)?;
-
-        self.body.send(stream).await?;
-
+        let mut data = Vec::new();
+        encode_request(self, &mut data);
+        stream.write_all(&data).await.map_err(|_| HttpError::WriteBodyError)?;
+        if let Some(body) = &self.body.stream {
+            body.send(stream, is_chunked(&self.headers), &self.trailers).await?;
+        }
         Ok(())
     }
     fn as_box(self) -> Box<dyn Sendable> {
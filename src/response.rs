@@ -1,12 +1,15 @@
-use super::{body::{Body, Part}, gen_multipart_boundary, headers::Headers, read_line_crlf, HttpError, Sendable};
+use super::{body::{Body, Part}, client::TlsInfo, codec::{encode_response, encode_response_without_body, is_chunked, parse_response_head, parse_status_code, put_stream_framing, BodyFraming}, gen_multipart_boundary, headers::Headers, read_head, HttpError, Sendable};
 
 use async_trait::async_trait;
 use tokio::io::{AsyncReadExt, AsyncWrite, AsyncWriteExt};
-use std::fmt::{Debug, Display};
+use std::{fmt::{Debug, Display}, net::SocketAddr};
 
 pub mod status_code {
+    pub const CONTINUE: &str = "100 Continue";
+    pub const EARLY_HINTS: &str = "103 Early Hints";
     pub const OK: &str = "200 OK";
     pub const NOT_FOUND: &str = "404 Not Found";
+    pub const SERVICE_UNAVAILABLE: &str = "503 Service Unavailable";
 }
 
 /// Http response
@@ -15,6 +18,11 @@ pub struct HttpResponse {
     pub status_code: String,
     pub headers: Headers,
     pub body: Body,
+    pub trailers: Headers,
+    /// Address of the server the client received response from
+    pub remote_addr: Option<SocketAddr>,
+    /// Details of tls connection the client received response over
+    pub tls: Option<TlsInfo>,
 }
 
 impl Display for HttpResponse {
@@ -24,28 +32,82 @@ impl Display for HttpResponse {
 }
 
 impl HttpResponse {
+    /// Create http response, streamed body without `Content-Length` is sent chunked
     pub fn new(
         status_code: &str,
-        headers: Headers,
+        mut headers: Headers,
         body: Body
     ) -> Self {
+        put_stream_framing(&mut headers, &body);
         HttpResponse {
             status_code: status_code.to_string(),
             headers,
-            body
+            body,
+            trailers: Headers::new(),
+            remote_addr: None,
+            tls: None
         }
     }
 
-    /// Read http response from stream
+    /// Read http response from stream, interim responses are skipped
     pub async fn recv(stream: &mut (impl AsyncReadExt + Unpin)) -> Result<HttpResponse, HttpError> {
-        let status = read_line_crlf(stream).await?;
+        Self::recv_final(stream, None, |_| {}).await
+    }
+
+    /// Read final http response from stream, interim responses are passed to the callback
+    ///
+    /// `method` is the method of the request, responses to `HEAD` are read without body
+    pub async fn recv_final(
+        stream: &mut (impl AsyncReadExt + Unpin),
+        method: Option<&str>,
+        mut on_interim: impl FnMut(&HttpResponse)
+    ) -> Result<HttpResponse, HttpError> {
+        loop {
+            let mut response = Self::recv_head(stream).await?;
+            if response.is_interim() {
+                on_interim(&response);
+                continue;
+            }
+            response.recv_body(stream, method).await?;
+            return Ok(response);
+        }
+    }
 
-        let (_, status_code) = status.split_once(" ").ok_or(HttpError::InvalidStatus)?;
+    /// Read http response status line and headers from stream, body is left empty
+    pub async fn recv_head(stream: &mut (impl AsyncReadExt + Unpin)) -> Result<HttpResponse, HttpError> {
+        let head = read_head(stream, true).await?;
+        let (head, _) = parse_response_head(&head)?.ok_or(HttpError::InvalidStatus)?;
 
-        let headers = Headers::recv(stream).await?;
-        let body = Body::recv(stream, &headers).await?;
+        Ok(HttpResponse::new(&head.status_code, head.headers, Body::default()))
+    }
 
-        Ok(HttpResponse::new(status_code, headers, body))
+    /// Read http response body and trailers from stream
+    ///
+    /// `method` is the method of the request, responses to `HEAD` have no body
+    pub async fn recv_body(&mut self, stream: &mut (impl AsyncReadExt + Unpin), method: Option<&str>) -> Result<(), HttpError> {
+        let framing = BodyFraming::from_response_head(&self.status_code, method, &self.headers)?;
+        (self.body, self.trailers) = Body::recv_framed(stream, framing).await?;
+        Ok(())
+    }
+
+    /// Get numeric status code
+    pub fn code(&self) -> Option<u16> {
+        parse_status_code(&self.status_code)
+    }
+
+    /// Send only status line and headers, as a response to `HEAD` request
+    pub async fn send_head(
+        &self,
+        stream: &mut (dyn AsyncWrite + Unpin + Send + Sync),
+    ) -> Result<(), HttpError> {
+        let mut data = Vec::new();
+        encode_response_without_body(self, &mut data);
+        stream.write_all(&data).await.map_err(|_| HttpError::WriteHeadError)
+    }
+
+    /// Is response interim (1xx except `101 Switching Protocols`), the final response follows it
+    pub fn is_interim(&self) -> bool {
+        self.code().is_some_and(|o| (100..200).contains(&o) && o != 101)
     }
 
     pub fn get_multipart(&self) -> Option<Vec<Part>> {
@@ -79,21 +141,18 @@ impl Sendable for HttpResponse {
         &self,
         stream: &mut (dyn AsyncWrite + Unpin + Send + Sync),
     ) -> Result<(), HttpError> {
-        let mut head: String = String::new();
-        head.push_str("HTTP/1.1 ");
-        head.push_str(&self.status_code);
-        head.push_str("\r\n");
-        stream.write_all(head.as_bytes()).await.map_err(|_| HttpError::WriteHeadError)?;
-
-        self.headers.send(stream).await?;
-
-        stream.write_all(b"\r\n").await.map_err(|_| HttpError::WriteBodyError)?;
-
-        self.body.send(stream).await?;
-
+        let mut data = Vec::new();
+        encode_response(self, &mut data);
+        stream.write_all(&data).await.map_err(|_| HttpError::WriteBodyError)?;
+        if let Some(body) = &self.body.stream {
+            body.send(stream, is_chunked(&self.headers), &self.trailers).await?;
+        }
         Ok(())
     }
     fn as_box(self) -> Box<dyn Sendable> {
         Box::new(self)
     }
+    fn as_response_mut(&mut self) -> Option<&mut HttpResponse> {
+        Some(self)
+    }
 }
@@ -1,27 +1,191 @@
 use super::{
-    HttpServer, 
+    HttpServer,
     super::{
         Stream,
-        request::HttpRequest
+        Sendable,
+        codec::{has_response_body, is_chunked, put_stream_framing},
+        body::Body,
+        error::HttpError,
+        headers::Headers,
+        request::HttpRequest,
+        response::{status_code::{CONTINUE, EARLY_HINTS}, HttpResponse}
     }
 };
 
-use std::{future::Future, pin::Pin, sync::Arc};
-use tokio::net::TcpStream;
-use tokio_io_timeout::TimeoutStream;
+use std::{future::Future, net::SocketAddr, pin::Pin, sync::Arc, time::Duration};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncWrite, BufReader},
+    sync::{mpsc, oneshot, watch, OwnedSemaphorePermit, Semaphore},
+    task::JoinHandle
+};
+use tokio_io_timeout::{TimeoutReader, TimeoutWriter};
+
+pub type Handler<T> = Box<dyn Fn(Arc<T>, Stream) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync>;
+
+/// Connection options set in [`HttpServerStarter`](super::starter::HttpServerStarter)
+#[derive(Debug, Clone)]
+pub struct ConnectionOptions {
+    /// Timeout for read & write
+    pub timeout: Option<Duration>,
+    /// Max count of pipelined requests processed at once, 0 means no limit
+    pub pipeline_limit: usize,
+    /// How long to wait for the next request on kept alive connection
+    pub keep_alive_timeout: Option<Duration>,
+    /// Max count of requests on one connection
+    pub max_requests: Option<usize>
+}
+
+impl ConnectionOptions {
+    /// Headers to add to the response, telling client if connection is kept alive
+    fn connection_headers(&self, req: &HttpRequest, keep_alive: bool, count: usize) -> Headers {
+        let mut headers = Headers::new();
 
-pub type Handler<T> = Box<dyn Fn(Arc<T>, TimeoutStream<TcpStream>) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync>;
+        if !keep_alive {
+            headers.put("Connection", "close".to_string());
+            return headers;
+        }
+
+        let mut params = Vec::new();
+        if let Some(timeout) = self.keep_alive_timeout {
+            // rounded up, `timeout=0` would tell that connection can't be reused
+            let secs = timeout.as_secs() + u64::from(timeout.subsec_nanos() > 0);
+            params.push(format!("timeout={}", secs.max(1)));
+        }
+        if let Some(max_requests) = self.max_requests {
+            params.push(format!("max={}", max_requests - count));
+        }
+
+        if req.version == "HTTP/1.0" || !params.is_empty() {
+            headers.put("Connection", "keep-alive".to_string());
+        }
+        if !params.is_empty() {
+            headers.put("Keep-Alive", params.join(", "));
+        }
+
+        headers
+    }
+}
+
+impl Default for ConnectionOptions {
+    fn default() -> Self {
+        ConnectionOptions {
+            timeout: None,
+            pipeline_limit: 1,
+            keep_alive_timeout: None,
+            max_requests: None
+        }
+    }
+}
+
+/// Response waiting to be written, in the order of requests
+enum Outgoing {
+    Interim(HttpResponse),
+    Reject {
+        method: String,
+        version: String,
+        connection: Headers,
+        response: Box<dyn Sendable>
+    },
+    Response {
+        method: String,
+        version: String,
+        interim: bool,
+        keep_alive: bool,
+        connection: Headers,
+        hints: oneshot::Receiver<Option<Headers>>,
+        response: JoinHandle<Option<Box<dyn Sendable>>>,
+        _permit: Option<OwnedSemaphorePermit>
+    }
+}
 
 /// Default connection handler
 /// Turns input to request and response to output
+///
+/// Uses default [`ConnectionOptions`], see [`handler_connection_with_options`]
 pub async fn handler_connection<S: HttpServer + Send + 'static + Sync>(
-    server: Arc<S>, 
-    mut sock: Stream
+    server: Arc<S>,
+    sock: Stream
+) {
+    handler_connection_with_options(server, sock, ConnectionOptions::default()).await
+}
+
+/// Default connection handler with options
+///
+/// Pipelined requests are read ahead and processed concurrently
+/// (up to [`pipeline_limit`](ConnectionOptions::pipeline_limit)), responses are written in order. \
+/// Connection is kept alive until client asks to close it, or until the limits in options
+pub async fn handler_connection_with_options<S: HttpServer + Send + 'static + Sync>(
+    server: Arc<S>,
+    mut sock: Stream,
+    options: ConnectionOptions
+) {
+    let addr = sock.get_ref().peer_addr();
+    let local_addr = sock.get_ref().local_addr();
+
+    let read_timeout = sock.read_timeout();
+    let write_timeout = sock.write_timeout();
+    let (reader, writer) = tokio::io::split(sock.get_mut());
+
+    let mut reader = TimeoutReader::new(reader);
+    reader.set_timeout(read_timeout);
+    let mut writer = TimeoutWriter::new(writer);
+    writer.set_timeout(write_timeout);
+
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let (written_sender, written) = watch::channel(0);
+
+    let reader = read_requests(server.clone(), addr, local_addr, Box::pin(reader), sender, written, options);
+    let writer = write_responses(server, Box::pin(writer), receiver, written_sender);
+    tokio::pin!(reader, writer);
+
+    tokio::select! {
+        _ = &mut reader => writer.await,
+        _ = &mut writer => {}
+    }
+}
+
+async fn read_requests<S: HttpServer + Send + 'static + Sync>(
+    server: Arc<S>,
+    addr: Option<SocketAddr>,
+    local_addr: Option<SocketAddr>,
+    reader: Pin<Box<TimeoutReader<impl AsyncRead>>>,
+    sender: mpsc::UnboundedSender<Outgoing>,
+    mut written: watch::Receiver<usize>,
+    options: ConnectionOptions
 ) {
-    let Ok(addr) = sock.get_ref().peer_addr() else { return; };
+    let read_timeout = reader.timeout();
+    let mut reader = BufReader::new(reader);
+    let limit = (options.pipeline_limit > 0).then(|| Arc::new(Semaphore::new(options.pipeline_limit)));
+    let mut count = 0;
 
     loop {
-        let req = match HttpRequest::recv(sock.get_mut(), &addr).await {
+        let permit = match &limit {
+            Some(limit) => match limit.clone().acquire_owned().await {
+                Ok(i) => Some(i),
+                Err(_) => return
+            },
+            None => None
+        };
+
+        if let (Some(timeout), true) = (options.keep_alive_timeout, count > 0) {
+            reader.get_mut().as_mut().set_timeout_pinned(None);
+            // connection is idle only after all responses are written
+            let idle = async {
+                if written.wait_for(|o| *o >= count).await.is_ok() {
+                    tokio::time::sleep(timeout).await;
+                }
+            };
+            let expired = tokio::select! {
+                _ = reader.fill_buf() => false,
+                _ = idle => true
+            };
+            reader.get_mut().as_mut().set_timeout_pinned(read_timeout);
+            if expired {
+                return;
+            }
+        }
+
+        let mut req = match HttpRequest::recv_head_from(&mut reader, addr).await {
             Ok(i) => i,
             Err(e) => {
                 server.on_error(e).await;
@@ -29,26 +193,149 @@ pub async fn handler_connection<S: HttpServer + Send + 'static + Sync>(
             }
         };
 
-        let resp = match server.on_request(&req).await {
-            Some(i) => i,
-            None => {
+        req.local_addr = local_addr;
+        count += 1;
+        let keep_alive = req.keep_alive() && options.max_requests.is_none_or(|o| count < o);
+        let connection = options.connection_headers(&req, keep_alive, count);
+        let interim = req.accepts_interim();
+
+        if req.expects_continue() && interim {
+            if let Some(response) = server.on_expect_continue(&req).await {
+                let connection = options.connection_headers(&req, false, count);
+                let _ = sender.send(Outgoing::Reject { method: req.method, version: req.version, connection, response });
                 return;
             }
-        };
 
-        match resp.send(sock.get_mut()).await {
-            Ok(_) => {},
-            Err(e) => {
-                server.on_error(e).await;
+            let resp = HttpResponse::new(CONTINUE, Headers::new(), Body::default());
+            if sender.send(Outgoing::Interim(resp)).is_err() {
                 return;
-            },
+            }
+        }
+
+        if let Err(e) = req.recv_body(&mut reader).await {
+            server.on_error(e).await;
+            return;
+        }
+
+        let (hints_sender, hints) = oneshot::channel();
+        let method = req.method.clone();
+        let version = req.version.clone();
+        let now_server = server.clone();
+
+        let response = tokio::spawn(async move {
+            let _ = hints_sender.send(now_server.on_early_hints(&req).await);
+            now_server.on_request(&req).await
+        });
+
+        let outgoing = Outgoing::Response { method, version, interim, keep_alive, connection, hints, response, _permit: permit };
+        if sender.send(outgoing).is_err() || !keep_alive {
+            return;
         }
     }
 }
 
+async fn write_responses<S: HttpServer + Send + 'static + Sync>(
+    server: Arc<S>,
+    mut writer: Pin<Box<TimeoutWriter<impl AsyncWrite + Send + Sync>>>,
+    mut receiver: mpsc::UnboundedReceiver<Outgoing>,
+    written: watch::Sender<usize>
+) {
+    while let Some(outgoing) = receiver.recv().await {
+        let result = match outgoing {
+            Outgoing::Interim(resp) => resp.send(&mut writer).await,
+            Outgoing::Reject { method, version, connection, response } => {
+                if let Err(e) = send_response(response, &method, &version, &connection, &mut writer).await {
+                    server.on_error(e).await;
+                }
+                return;
+            }
+            Outgoing::Response { method, version, interim, keep_alive, connection, hints, response, _permit } => {
+                if let (Ok(Some(headers)), true) = (hints.await, interim) {
+                    let resp = HttpResponse::new(EARLY_HINTS, headers, Body::default());
+                    if let Err(e) = resp.send(&mut writer).await {
+                        server.on_error(e).await;
+                        return;
+                    }
+                }
+
+                match response.await {
+                    Ok(Some(resp)) => match send_response(resp, &method, &version, &connection, &mut writer).await {
+                        Ok(close) if close || !keep_alive => return,
+                        Ok(_) => {
+                            written.send_modify(|o| *o += 1);
+                            Ok(())
+                        },
+                        Err(e) => Err(e)
+                    },
+                    _ => return
+                }
+            }
+        };
+
+        if let Err(e) = result {
+            server.on_error(e).await;
+            return;
+        }
+    }
+}
+
+/// Send response adjusted to the request, returns if connection has to be closed
+async fn send_response(
+    mut resp: Box<dyn Sendable>,
+    method: &str,
+    version: &str,
+    connection: &Headers,
+    writer: &mut (impl AsyncWrite + Unpin + Send + Sync)
+) -> Result<bool, HttpError> {
+    let Some(resp) = resp.as_response_mut() else {
+        resp.send(writer).await?;
+        return Ok(false);
+    };
+
+    for (key, value) in connection.entries() {
+        resp.headers.put_default(key, value);
+    }
+
+    if version == "HTTP/1.0" {
+        // http/1.0 clients don't know chunked encoding, streamed body is sent until connection is closed
+        if is_chunked(&resp.headers) {
+            resp.headers.remove("transfer-encoding");
+            if !resp.body.is_stream() {
+                resp.headers.put("Content-Length", resp.body.data.len().to_string());
+            }
+        }
+        if resp.body.is_stream() && resp.headers.get("content-length").is_empty() {
+            resp.headers.put("Connection", "close".to_string());
+            resp.headers.remove("keep-alive");
+        }
+    } else {
+        put_stream_framing(&mut resp.headers, &resp.body);
+    }
+
+    // without framing client can't know where body ends, HEAD gets the same headers as GET
+    let code = resp.code().unwrap_or(200);
+    if has_response_body(code, None) && !is_chunked(&resp.headers) && !resp.body.is_stream() {
+        resp.headers.put_default("Content-Length", resp.body.data.len().to_string());
+    }
+    let close = resp.headers.get("connection").iter().any(|o| o.eq_ignore_ascii_case("close"));
+
+    if has_response_body(code, Some(method)) {
+        resp.send(writer).await?;
+    } else {
+        resp.send_head(writer).await?;
+    }
+
+    Ok(close)
+}
+
+/// Default handler using the options of [`HttpServerStarter`](super::starter::HttpServerStarter)
+pub(crate) fn options_handler<S: HttpServer + Send + 'static + Sync>(options: ConnectionOptions) -> Handler<S> {
+    Box::new(move |server, sock| Box::pin(handler_connection_with_options(server, sock, options.clone())))
+}
+
 #[macro_export]
 macro_rules! pin_handler {
     ($handler: expr) => {
         Box::new(move |a, b| Box::pin($handler(a, b)))
     };
-}
\ No newline at end of file
+}
@@ -0,0 +1,178 @@
+use super::{
+    HttpServer,
+    listener::{accept_any, Connection, Listener},
+    super::{
+        Sendable,
+        body::Body,
+        error::HttpError,
+        headers::Headers,
+        response::{status_code::SERVICE_UNAVAILABLE, HttpResponse}
+    }
+};
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    net::IpAddr,
+    sync::{Arc, Mutex},
+    time::Duration
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    sync::{OwnedSemaphorePermit, Semaphore}
+};
+
+const MIN_BACKOFF: Duration = Duration::from_millis(10);
+const MAX_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Max count of connections getting `503 Service Unavailable` at once, others are just closed
+const MAX_REJECTIONS: usize = 64;
+/// Max count of bytes read from rejected connection before closing it
+const MAX_REJECT_DRAIN: usize = 64 * 1024;
+
+/// Limits of simultaneous connections
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionLimits {
+    /// Max count of connections at once
+    pub max_connections: Option<usize>,
+    /// Max count of connections at once from one ip address
+    pub max_connections_per_ip: Option<usize>,
+    /// Respond `503 Service Unavailable` when there are too many connections,
+    /// instead of waiting for some to close before accepting new ones
+    pub reject_overload: bool
+}
+
+/// Accepts connections within the limits
+pub(crate) struct Limiter {
+    limits: ConnectionLimits,
+    connections: Option<Arc<Semaphore>>,
+    per_ip: Arc<Mutex<HashMap<IpAddr, usize>>>,
+    backoff: Duration,
+    next_listener: usize,
+    rejections: Arc<Semaphore>
+}
+
+/// Connection slot, released on drop
+pub(crate) struct ConnectionGuard {
+    _permit: Option<OwnedSemaphorePermit>,
+    ip: Option<IpAddr>,
+    per_ip: Arc<Mutex<HashMap<IpAddr, usize>>>
+}
+
+impl Limiter {
+    pub(crate) fn new(limits: ConnectionLimits) -> Self {
+        Limiter {
+            connections: limits.max_connections.map(|o| Arc::new(Semaphore::new(o))),
+            limits,
+            per_ip: Arc::new(Mutex::new(HashMap::new())),
+            backoff: MIN_BACKOFF,
+            next_listener: 0,
+            rejections: Arc::new(Semaphore::new(MAX_REJECTIONS))
+        }
+    }
+
+    /// Accept next connection
+    ///
+    /// Returns `None` if it was rejected or accept failed (then it waits with growing backoff)
+    pub(crate) async fn accept<T: HttpServer + Send + Sync>(
+        &mut self,
+        listeners: &[Listener],
+        server: &T
+    ) -> Option<(Connection, ConnectionGuard)> {
+        let mut permit = match (&self.connections, self.limits.reject_overload) {
+            (Some(connections), false) => Some(connections.clone().acquire_owned().await.ok()?),
+            _ => None
+        };
+
+        let sock = match accept_any(listeners, &mut self.next_listener).await {
+            Ok(i) => i,
+            Err(_) => {
+                server.on_error(HttpError::AcceptError).await;
+                tokio::time::sleep(self.backoff).await;
+                self.backoff = (self.backoff * 2).min(MAX_BACKOFF);
+                return None;
+            }
+        };
+        self.backoff = MIN_BACKOFF;
+
+        if let (Some(connections), true) = (&self.connections, self.limits.reject_overload) {
+            match connections.clone().try_acquire_owned() {
+                Ok(i) => permit = Some(i),
+                Err(_) => {
+                    self.reject(sock);
+                    return None;
+                }
+            }
+        }
+
+        let mut ip = None;
+        if let (Some(max), Some(addr)) = (self.limits.max_connections_per_ip, sock.peer_ip()) {
+            let mut per_ip = self.per_ip.lock().unwrap();
+            let count = per_ip.entry(addr).or_insert(0);
+            if *count >= max {
+                self.reject(sock);
+                return None;
+            }
+            *count += 1;
+            ip = Some(addr);
+        }
+
+        Some((sock, ConnectionGuard {
+            _permit: permit,
+            ip,
+            per_ip: self.per_ip.clone()
+        }))
+    }
+
+    /// Respond `503 Service Unavailable` and close the connection
+    ///
+    /// Request sent by the client is drained after the response, so closing doesn't reset the connection
+    fn reject(&self, sock: Connection) {
+        let Ok(permit) = self.rejections.clone().try_acquire_owned() else { return };
+        let mut sock = sock.into_transport();
+        tokio::spawn(async move {
+            let resp = HttpResponse::new(
+                SERVICE_UNAVAILABLE,
+                Headers::from(vec![("Content-Length", "0"), ("Connection", "close")]),
+                Body::default()
+            );
+            let _ = tokio::time::timeout(MAX_BACKOFF, async {
+                resp.send(&mut sock).await?;
+                sock.shutdown().await.map_err(|_| HttpError::ShutdownError)?;
+                let mut buf = [0; 4096];
+                let mut size = 0;
+                while size < MAX_REJECT_DRAIN {
+                    match sock.read(&mut buf).await {
+                        Ok(0) | Err(_) => break,
+                        Ok(count) => size += count
+                    }
+                }
+                Ok::<_, HttpError>(())
+            }).await;
+            drop(permit);
+        });
+    }
+}
+
+impl ConnectionGuard {
+    /// Hold the slot until the future is done
+    pub(crate) async fn hold<F: Future>(self, future: F) -> F::Output {
+        let output = future.await;
+        drop(self);
+        output
+    }
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        let Some(ip) = self.ip else { return };
+        let mut per_ip = self.per_ip.lock().unwrap();
+        if let Some(count) = per_ip.get_mut(&ip) {
+            *count -= 1;
+            if *count == 0 {
+                per_ip.remove(&ip);
+            }
+        }
+    }
+}
+
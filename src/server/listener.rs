@@ -0,0 +1,278 @@
+use super::super::Stream;
+
+use std::{
+    future::poll_fn,
+    io,
+    net::{IpAddr, SocketAddr, ToSocketAddrs},
+    path::PathBuf,
+    task::Poll
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::{TcpListener, TcpStream}
+};
+#[cfg(unix)]
+use tokio::net::{UnixListener, UnixStream};
+use tokio_io_timeout::TimeoutStream;
+
+/// Prefix of hosts that are unix socket paths, like `unix:/run/site.sock`
+pub const UNIX_PREFIX: &str = "unix:";
+
+/// Connection transport the server handles requests on
+pub trait Transport: AsyncRead + AsyncWrite + Unpin + Send + Sync {
+    /// Get address of the client, `None` if transport has no ip addresses
+    fn peer_addr(&self) -> Option<SocketAddr>;
+    /// Get address of the server side, `None` if transport has no ip addresses
+    fn local_addr(&self) -> Option<SocketAddr>;
+}
+
+impl Transport for TcpStream {
+    fn peer_addr(&self) -> Option<SocketAddr> {
+        TcpStream::peer_addr(self).ok()
+    }
+
+    fn local_addr(&self) -> Option<SocketAddr> {
+        TcpStream::local_addr(self).ok()
+    }
+}
+
+#[cfg(unix)]
+impl Transport for UnixStream {
+    fn peer_addr(&self) -> Option<SocketAddr> {
+        None
+    }
+
+    fn local_addr(&self) -> Option<SocketAddr> {
+        None
+    }
+}
+
+/// Unix socket options
+#[derive(Debug, Clone, Default)]
+pub struct UnixSocketOptions {
+    /// Permissions of the socket file, like `0o660`
+    pub permissions: Option<u32>,
+    /// Remove stale socket file before binding, and the socket file after the server is closed
+    pub cleanup: bool
+}
+
+/// Accepted connection
+pub(crate) enum Connection {
+    Tcp(TcpStream, SocketAddr),
+    #[cfg(unix)]
+    Unix(UnixStream)
+}
+
+/// Accepted connection, detached from the runtime
+pub(crate) enum StdConnection {
+    Tcp(std::net::TcpStream, SocketAddr),
+    #[cfg(unix)]
+    Unix(std::os::unix::net::UnixStream)
+}
+
+impl Connection {
+    /// Get ip address of the client
+    pub(crate) fn peer_ip(&self) -> Option<IpAddr> {
+        match self {
+            Connection::Tcp(_, addr) => Some(addr.ip()),
+            #[cfg(unix)]
+            Connection::Unix(_) => None
+        }
+    }
+
+    pub(crate) fn into_transport(self) -> Box<dyn Transport> {
+        match self {
+            Connection::Tcp(sock, _) => Box::new(sock),
+            #[cfg(unix)]
+            Connection::Unix(sock) => Box::new(sock)
+        }
+    }
+
+    /// Wrap into stream with read & write timeout
+    pub(crate) fn into_stream(self, timeout: Option<std::time::Duration>) -> Stream {
+        let mut sock = TimeoutStream::new(self.into_transport());
+        sock.set_read_timeout(timeout);
+        sock.set_write_timeout(timeout);
+        sock
+    }
+
+    /// Detach from the runtime, to move it to another one
+    pub(crate) fn into_std(self) -> io::Result<StdConnection> {
+        Ok(match self {
+            Connection::Tcp(sock, addr) => StdConnection::Tcp(sock.into_std()?, addr),
+            #[cfg(unix)]
+            Connection::Unix(sock) => StdConnection::Unix(sock.into_std()?)
+        })
+    }
+}
+
+impl StdConnection {
+    /// Register in the current runtime
+    pub(crate) fn into_tokio(self) -> io::Result<Connection> {
+        Ok(match self {
+            StdConnection::Tcp(sock, addr) => Connection::Tcp(TcpStream::from_std(sock)?, addr),
+            #[cfg(unix)]
+            StdConnection::Unix(sock) => Connection::Unix(UnixStream::from_std(sock)?)
+        })
+    }
+}
+
+/// Socket file removed on drop
+pub(crate) struct SocketFile(PathBuf);
+
+impl Drop for SocketFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// Listener bound on one host
+enum StdListener {
+    Tcp(std::net::TcpListener),
+    #[cfg(unix)]
+    Unix(std::os::unix::net::UnixListener, Option<SocketFile>)
+}
+
+/// Listener registered in the runtime
+pub(crate) enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix { listener: UnixListener, _file: Option<SocketFile> }
+}
+
+impl Listener {
+    fn poll_accept(&self, cx: &mut std::task::Context<'_>) -> Poll<io::Result<Connection>> {
+        match self {
+            Listener::Tcp(listener) => listener.poll_accept(cx).map_ok(|(sock, addr)| Connection::Tcp(sock, addr)),
+            #[cfg(unix)]
+            Listener::Unix { listener, .. } => listener.poll_accept(cx).map_ok(|(sock, _)| Connection::Unix(sock))
+        }
+    }
+}
+
+/// Listeners bound on server hosts, before they are moved to the runtime
+pub(crate) struct Listeners {
+    hosts: Vec<String>,
+    listeners: Vec<StdListener>
+}
+
+impl Listeners {
+    /// Bind listener on every host
+    ///
+    /// Hosts starting with [`UNIX_PREFIX`] are bound as unix sockets
+    pub(crate) fn bind(hosts: &[String], unix: &UnixSocketOptions) -> io::Result<Self> {
+        let mut listeners = Vec::with_capacity(hosts.len());
+
+        for host in hosts {
+            let listener = match host.strip_prefix(UNIX_PREFIX) {
+                Some(path) => bind_unix(path, unix)?,
+                None => StdListener::Tcp(bind_tcp(host)?)
+            };
+            listeners.push(listener);
+        }
+
+        Ok(Listeners { hosts: hosts.to_vec(), listeners })
+    }
+
+    /// Get hosts the listeners were bound on
+    pub(crate) fn hosts(&self) -> &[String] {
+        &self.hosts
+    }
+
+    /// Get actual local addresses of the tcp listeners
+    pub(crate) fn local_addrs(&self) -> Vec<SocketAddr> {
+        self.listeners.iter().filter_map(|o| match o {
+            StdListener::Tcp(listener) => listener.local_addr().ok(),
+            #[cfg(unix)]
+            StdListener::Unix(..) => None
+        }).collect()
+    }
+
+    /// Register listeners in the current runtime
+    pub(crate) fn into_tokio(self) -> io::Result<Vec<Listener>> {
+        self.listeners.into_iter().map(|o| Ok(match o {
+            StdListener::Tcp(listener) => Listener::Tcp(TcpListener::from_std(listener)?),
+            #[cfg(unix)]
+            StdListener::Unix(listener, file) => Listener::Unix { listener: UnixListener::from_std(listener)?, _file: file }
+        })).collect()
+    }
+}
+
+/// Bind tcp listener on the first address of host that can be bound
+///
+/// `SO_REUSEADDR` is set on unix like tokio does, so restarted server can bind while old connections are in `TIME_WAIT`
+fn bind_tcp(host: &str) -> io::Result<std::net::TcpListener> {
+    let mut result = Err(io::Error::new(io::ErrorKind::InvalidInput, "host has no addresses"));
+
+    for addr in host.to_socket_addrs()? {
+        result = (|| {
+            let socket = socket2::Socket::new(socket2::Domain::for_address(addr), socket2::Type::STREAM, Some(socket2::Protocol::TCP))?;
+            #[cfg(unix)]
+            socket.set_reuse_address(true)?;
+            socket.bind(&addr.into())?;
+            socket.listen(1024)?;
+            socket.set_nonblocking(true)?;
+            Ok(socket.into())
+        })();
+        if result.is_ok() {
+            break;
+        }
+    }
+
+    result
+}
+
+#[cfg(unix)]
+fn bind_unix(path: &str, options: &UnixSocketOptions) -> io::Result<StdListener> {
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+    if options.cleanup {
+        match std::fs::symlink_metadata(path) {
+            Ok(meta) if meta.file_type().is_socket() => std::fs::remove_file(path)?,
+            Ok(_) => return Err(io::Error::new(io::ErrorKind::AlreadyExists, "unix socket path exists and is not a socket")),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {},
+            Err(e) => return Err(e)
+        }
+    }
+
+    let listener = match options.permissions {
+        Some(permissions) => {
+            // bind on a temporary path and link it into place once the permissions are set,
+            // so the socket is never reachable with the default ones
+            let temp = format!("{path}.{}.tmp", std::process::id());
+            let _ = std::fs::remove_file(&temp);
+            let listener = std::os::unix::net::UnixListener::bind(&temp)?;
+            let result = std::fs::set_permissions(&temp, std::fs::Permissions::from_mode(permissions))
+                .and_then(|_| std::fs::hard_link(&temp, path));
+            std::fs::remove_file(&temp)?;
+            result?;
+            listener
+        },
+        None => std::os::unix::net::UnixListener::bind(path)?
+    };
+    listener.set_nonblocking(true)?;
+
+    Ok(StdListener::Unix(listener, options.cleanup.then(|| SocketFile(PathBuf::from(path)))))
+}
+
+#[cfg(not(unix))]
+fn bind_unix(_: &str, _: &UnixSocketOptions) -> io::Result<StdListener> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "unix sockets are not supported"))
+}
+
+/// Accept connection on any of the listeners
+///
+/// Listeners are checked starting from `next`, which is moved past the one that accepted,
+/// so busy listener doesn't starve the others
+pub(crate) async fn accept_any(listeners: &[Listener], next: &mut usize) -> io::Result<Connection> {
+    poll_fn(|cx| {
+        for i in 0..listeners.len() {
+            let index = (*next + i) % listeners.len();
+            if let Poll::Ready(result) = listeners[index].poll_accept(cx) {
+                *next = (index + 1) % listeners.len();
+                return Poll::Ready(result);
+            }
+        }
+        Poll::Pending
+    }).await
+}
@@ -3,25 +3,27 @@ use std::{
     boxed::Box,
     error::Error,
     sync::Arc,
-    time::Duration,
 };
 
 use async_trait::async_trait;
-use threadpool::ThreadPool;
-use tokio::net::TcpListener;
-use tokio::runtime::Handle;
-use tokio_io_timeout::TimeoutStream;
 
 use crate::pin_handler;
 
 use super::error::HttpError;
+use super::headers::Headers;
 use super::request::HttpRequest;
 use super::Sendable;
 
 pub mod handler;
+pub mod limits;
+pub mod listener;
 pub mod starter;
+mod workers;
 
-use handler::{handler_connection, Handler};
+use handler::{handler_connection, ConnectionOptions, Handler};
+use limits::{ConnectionLimits, Limiter};
+use listener::{Listeners, UnixSocketOptions};
+use workers::WorkerPool;
 
 /// Async http server trait
 #[async_trait]
@@ -36,12 +38,35 @@ pub trait HttpServer {
         &self, 
         _: HttpError
     ) -> () {}
+
+    /// Called when request has `Expect: 100-continue` header, before its body is read
+    ///
+    /// Return a final response to reject the body (the connection is closed after it),
+    /// `None` sends `100 Continue` and reads the body
+    async fn on_expect_continue(
+        &self,
+        _: &HttpRequest
+    ) -> Option<Box<dyn Sendable>> {
+        None
+    }
+
+    /// Called before [`on_request`](HttpServer::on_request)
+    ///
+    /// Returned headers (like `Link`) are sent in `103 Early Hints` response
+    /// while the final response is being prepared
+    async fn on_early_hints(
+        &self,
+        _: &HttpRequest
+    ) -> Option<Headers> {
+        None
+    }
 }
 
-async fn start_server_with_threadpool<T>(
+async fn start_server_with_workers<T>(
     server: T,
-    host: &str,
-    timeout: Option<Duration>,
+    listeners: Listeners,
+    options: ConnectionOptions,
+    limits: ConnectionLimits,
     threads: usize,
     handler: Handler<T>,
     running: Arc<AtomicBool>,
@@ -49,35 +74,22 @@ async fn start_server_with_threadpool<T>(
 where
     T: HttpServer + Send + 'static + Sync,
 {
-    let threadpool = ThreadPool::new(threads);
-
     let server = Arc::new(server);
-    let listener = TcpListener::bind(host).await?;
-    let old_handler = handler;
-    let handler = Arc::new(move |now_server, sock| { 
-        Handle::current().block_on(old_handler(now_server, sock)); 
-    });
+    let hosts = listeners.hosts().to_vec();
+    let listeners = listeners.into_tokio()?;
+    let mut limiter = Limiter::new(limits);
+    let workers = WorkerPool::new(threads, server.clone(), handler, options)?;
 
-    let host_clone = String::from(host).clone();
-    let server_clone = server.clone();
-    server_clone.on_start(&host_clone).await;
+    for host in &hosts {
+        server.on_start(host).await;
+    }
 
     while running.load(Ordering::Acquire) {
-        let Ok((sock, _)) = listener.accept().await else { continue; };
-        let mut sock = TimeoutStream::new(sock);
-
-        sock.set_read_timeout(timeout);
-        sock.set_write_timeout(timeout);
-
-        let now_server = Arc::clone(&server);
-        let now_handler = Arc::clone(&handler);
-
-        threadpool.execute(move || {
-            (now_handler)(now_server, sock);
-        });
+        let Some((sock, guard)) = limiter.accept(&listeners, server.as_ref()).await else { continue; };
+        workers.dispatch(sock, guard);
     }
 
-    threadpool.join();
+    workers.join().await;
 
     server.on_close().await;
 
@@ -86,65 +98,30 @@ where
 
 async fn start_server_new_thread<T>(
     server: T,
-    host: &str,
-    timeout: Option<Duration>,
+    listeners: Listeners,
+    options: ConnectionOptions,
+    limits: ConnectionLimits,
     handler: Handler<T>,
     running: Arc<AtomicBool>,
 ) -> Result<(), Box<dyn Error>>
 where
-    T: HttpServer + Send + 'static,
+    T: HttpServer + Send + 'static + Sync,
 {
     let server = Arc::new(server);
-    let listener = TcpListener::bind(host).await?;
-
-    let host_clone = String::from(host).clone();
-    let server_clone = server.clone();
-    server_clone.on_start(&host_clone).await;
+    let hosts = listeners.hosts().to_vec();
+    let listeners = listeners.into_tokio()?;
+    let mut limiter = Limiter::new(limits);
 
-    while running.load(Ordering::Acquire) {
-        let Ok((sock, _)) = listener.accept().await else { continue; };
-        let mut sock = TimeoutStream::new(sock);
-
-        sock.set_read_timeout(timeout);
-        sock.set_write_timeout(timeout);
-
-        let now_server = Arc::clone(&server);
-
-        tokio::spawn((&handler)(now_server, sock));
+    for host in &hosts {
+        server.on_start(host).await;
     }
 
-    server.on_close().await;
-
-    Ok(())
-}
-
-async fn start_server_sync<T>(
-    server: T,
-    host: &str,
-    timeout: Option<Duration>,
-    handler: Handler<T>,
-    running: Arc<AtomicBool>,
-) -> Result<(), Box<dyn Error>>
-where
-    T: HttpServer + Send + 'static,
-{
-    let server = Arc::new(server);
-    let listener = TcpListener::bind(host).await?;
-
-    let host_clone = String::from(host).clone();
-    let server_clone = server.clone();
-    server_clone.on_start(&host_clone).await;
-
     while running.load(Ordering::Acquire) {
-        let Ok((sock, _)) = listener.accept().await else { continue; };
-        let mut sock = TimeoutStream::new(sock);
-
-        sock.set_read_timeout(timeout);
-        sock.set_write_timeout(timeout);
-
+        let Some((sock, guard)) = limiter.accept(&listeners, server.as_ref()).await else { continue; };
+        let sock = sock.into_stream(options.timeout);
         let now_server = Arc::clone(&server);
 
-        handler(now_server, sock).await;
+        tokio::spawn(guard.hold((&handler)(now_server, sock)));
     }
 
     server.on_close().await;
@@ -161,8 +138,9 @@ pub async fn start_server<T: HttpServer + Send + 'static + Sync>(
 ) -> Result<(), Box<dyn Error>> {
     start_server_new_thread(
         server,
-        host,
-        None,
+        Listeners::bind(&[host.to_string()], &UnixSocketOptions::default())?,
+        ConnectionOptions::default(),
+        ConnectionLimits::default(),
         pin_handler!(handler_connection),
         Arc::new(AtomicBool::new(true)),
     ).await
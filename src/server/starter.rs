@@ -2,15 +2,16 @@ use tokio::task::JoinHandle;
 
 use super::{
     start_server_new_thread, 
-    start_server_sync, 
-    start_server_with_threadpool, 
-    handler::{handler_connection, Handler}, 
+    start_server_with_workers, 
+    handler::{handler_connection, options_handler, ConnectionOptions, Handler}, 
+    limits::ConnectionLimits,
+    listener::{Listeners, UnixSocketOptions},
     HttpServer
 };
 use crate::pin_handler;
 
 use std::{
-    error::Error, sync::{
+    error::Error, io, net::SocketAddr, sync::{
         atomic::{AtomicBool, Ordering},
         Arc,
     }, time::Duration
@@ -20,11 +21,19 @@ use std::{
 pub struct RunningHttpServer {
     thread: JoinHandle<()>,
     running: Arc<AtomicBool>,
+    local_addrs: Vec<SocketAddr>,
 }
 
 impl RunningHttpServer {
-    fn new(thread: JoinHandle<()>, running: Arc<AtomicBool>) -> Self {
-        RunningHttpServer { thread, running }
+    fn new(thread: JoinHandle<()>, running: Arc<AtomicBool>, local_addrs: Vec<SocketAddr>) -> Self {
+        RunningHttpServer { thread, running, local_addrs }
+    }
+
+    /// Get addresses of the tcp hosts the server is listening on, in the order of hosts
+    ///
+    /// Unix socket hosts are skipped. Hosts with port 0 get the actual port here
+    pub fn local_addrs(&self) -> &[SocketAddr] {
+        &self.local_addrs
     }
 
     /// Stop http server
@@ -38,9 +47,12 @@ impl RunningHttpServer {
 pub struct HttpServerStarter<T: HttpServer + Send + 'static> {
     http_server: T,
     handler: Handler<T>,
-    timeout: Option<Duration>,
-    host: String,
+    custom_handler: bool,
+    hosts: Vec<String>,
     threads: usize,
+    options: ConnectionOptions,
+    limits: ConnectionLimits,
+    unix: UnixSocketOptions,
 }
 
 impl<T: HttpServer + Send + 'static + Sync> HttpServerStarter<T> {
@@ -49,9 +61,12 @@ impl<T: HttpServer + Send + 'static + Sync> HttpServerStarter<T> {
         HttpServerStarter {
             http_server,
             handler: pin_handler!(handler_connection),
-            timeout: None,
-            host: host.to_string(),
+            custom_handler: false,
+            hosts: vec![host.to_string()],
             threads: 0,
+            options: ConnectionOptions::default(),
+            limits: ConnectionLimits::default(),
+            unix: UnixSocketOptions::default(),
         }
     }
 
@@ -62,32 +77,101 @@ impl<T: HttpServer + Send + 'static + Sync> HttpServerStarter<T> {
     }
 
     /// Set if http_rrs is supported
+    ///
+    /// Pipelining and keep-alive options are used only by the default handler
     pub fn handler(mut self, handler: Handler<T>) -> Self {
         self.handler = handler;
+        self.custom_handler = true;
         self
     }
 
     /// Set timeout for read & write
     pub fn timeout(mut self, timeout: Option<Duration>) -> Self {
-        self.timeout = timeout;
+        self.options.timeout = timeout;
         self
     }
 
-    /// Set host
+    /// Set host, replacing all other hosts
     pub fn host(mut self, host: String) -> Self {
-        self.host = host;
+        self.hosts = vec![host];
         self
     }
 
-    /// Set threads in threadpool and return builder
+    /// Add one more host to listen on
     ///
-    /// 0 threads means that a new thread is created for each connection \
-    /// 1 thread means that all connections are processed in the main thread
+    /// Hosts like `unix:/run/site.sock` are bound as unix sockets
+    pub fn bind(mut self, host: &str) -> Self {
+        self.hosts.push(host.to_string());
+        self
+    }
+
+    /// Set permissions of unix socket files, like `0o660`
+    pub fn unix_permissions(mut self, permissions: Option<u32>) -> Self {
+        self.unix.permissions = permissions;
+        self
+    }
+
+    /// Set if stale unix socket files are removed before binding, and removed after the server is closed
+    pub fn unix_cleanup(mut self, cleanup: bool) -> Self {
+        self.unix.cleanup = cleanup;
+        self
+    }
+
+    /// Set worker threads and return builder
+    ///
+    /// 0 threads means that connections are spawned as tasks on the current runtime \
+    /// 1 or more threads start a runtime on each thread, new connections go to the least loaded one
     pub fn threads(mut self, threads: usize) -> Self {
         self.threads = threads;
         self
     }
 
+    /// Set max count of pipelined requests processed at once on one connection
+    ///
+    /// 1 (default) means that requests are processed one by one \
+    /// 0 means no limit
+    pub fn pipeline_limit(mut self, limit: usize) -> Self {
+        self.options.pipeline_limit = limit;
+        self
+    }
+
+    /// Set how long to wait for the next request on kept alive connection
+    ///
+    /// It is separate from the read timeout, which applies while request is being read
+    pub fn keep_alive_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.options.keep_alive_timeout = timeout;
+        self
+    }
+
+    /// Set max count of requests on one connection, it is closed after the last response
+    pub fn max_requests(mut self, max_requests: Option<usize>) -> Self {
+        self.options.max_requests = max_requests;
+        self
+    }
+
+    /// Set max count of connections at once
+    ///
+    /// When reached, new connections wait until some are closed
+    pub fn max_connections(mut self, max_connections: Option<usize>) -> Self {
+        self.limits.max_connections = max_connections;
+        self
+    }
+
+    /// Set max count of connections at once from one ip address
+    ///
+    /// Extra connections get `503 Service Unavailable` response
+    pub fn max_connections_per_ip(mut self, max_connections: Option<usize>) -> Self {
+        self.limits.max_connections_per_ip = max_connections;
+        self
+    }
+
+    /// Set if connections over [`max_connections`](Self::max_connections) get
+    /// `503 Service Unavailable` response instead of waiting
+    pub fn reject_overload(mut self, reject: bool) -> Self {
+        self.limits.reject_overload = reject;
+        self
+    }
+
     /// Get http server
     pub fn get_http_server(&self) -> &T {
         &self.http_server
@@ -100,83 +184,107 @@ impl<T: HttpServer + Send + 'static + Sync> HttpServerStarter<T> {
 
     /// Get timeout for read & write
     pub fn get_timeout(&self) -> Option<Duration> {
-        self.timeout
+        self.options.timeout
     }
 
-    /// Get host
+    /// Get first host
     pub fn get_host(&self) -> &str {
-        &self.host
+        &self.hosts[0]
+    }
+
+    /// Get all hosts to listen on
+    pub fn get_hosts(&self) -> &[String] {
+        &self.hosts
     }
 
-    /// Get threads in threadpool
+    /// Get worker threads
     ///
-    /// 0 threads means that a new thread is created for each connection \
-    /// 1 thread means that all connections are processed in the one thread
+    /// 0 threads means that connections are spawned as tasks on the current runtime \
+    /// 1 or more threads start a runtime on each thread
     pub fn get_threads(&self) -> usize {
         self.threads
     }
 
+    /// Get max count of pipelined requests processed at once on one connection
+    pub fn get_pipeline_limit(&self) -> usize {
+        self.options.pipeline_limit
+    }
+
+    /// Get how long to wait for the next request on kept alive connection
+    pub fn get_keep_alive_timeout(&self) -> Option<Duration> {
+        self.options.keep_alive_timeout
+    }
+
+    /// Get max count of requests on one connection
+    pub fn get_max_requests(&self) -> Option<usize> {
+        self.options.max_requests
+    }
+
+    /// Get max count of connections at once
+    pub fn get_max_connections(&self) -> Option<usize> {
+        self.limits.max_connections
+    }
+
+    /// Get max count of connections at once from one ip address
+    pub fn get_max_connections_per_ip(&self) -> Option<usize> {
+        self.limits.max_connections_per_ip
+    }
+
+    /// Get if connections over max connections get `503 Service Unavailable` response
+    pub fn get_reject_overload(&self) -> bool {
+        self.limits.reject_overload
+    }
+
     /// Start http server forever with options
     pub async fn start_forever(self) -> Result<(), Box<dyn Error>> {
         let running = Arc::new(AtomicBool::new(true));
-
-        if self.threads == 0 {
-            start_server_new_thread(self.http_server, &self.host, self.timeout, self.handler, running).await
-        } else if self.threads == 1 {
-            start_server_sync(self.http_server, &self.host, self.timeout, self.handler, running).await
-        } else {
-            start_server_with_threadpool(
-                self.http_server,
-                &self.host,
-                self.timeout,
-                self.threads,
-                self.handler,
-                running,
-            ).await
-        }
+        let listeners = Listeners::bind(&self.hosts, &self.unix)?;
+        self.run(listeners, running).await
     }
 
     /// Start http server with options in new thread
+    ///
+    /// Listeners are bound right away, so [`local_addrs`](RunningHttpServer::local_addrs) are known. \
+    /// Panics if some host can't be bound, use [`try_start`](Self::try_start) to get the error
     pub fn start(self) -> RunningHttpServer {
+        self.try_start().expect("http server error")
+    }
+
+    /// Start http server with options in new thread, or return error of binding the hosts
+    pub fn try_start(self) -> io::Result<RunningHttpServer> {
         let running = Arc::new(AtomicBool::new(true));
         let running_clone = running.clone();
 
-        let thread = if self.threads == 0 {
-            tokio::spawn(async move {
-                start_server_new_thread(
-                    self.http_server,
-                    &self.host,
-                    self.timeout,
-                    self.handler,
-                    running_clone,
-                ).await
-                .expect("http server error");
-            })
-        } else if self.threads == 1 {
-            tokio::spawn(async move {
-                start_server_sync(
-                    self.http_server,
-                    &self.host,
-                    self.timeout,
-                    self.handler,
-                    running_clone,
-                ).await
+        let listeners = Listeners::bind(&self.hosts, &self.unix)?;
+        let local_addrs = listeners.local_addrs();
+
+        let thread = tokio::spawn(async move {
+            self.run(listeners, running_clone).await
                 .expect("http server error");
-            })
-        } else {
-            tokio::spawn(async move {
-                start_server_with_threadpool(
-                    self.http_server,
-                    &self.host,
-                    self.timeout,
-                    self.threads,
-                    self.handler,
-                    running_clone,
-                ).await
-                .expect("http server error")
-            })
+        });
+
+        Ok(RunningHttpServer::new(thread, running, local_addrs))
+    }
+
+    async fn run(self, listeners: Listeners, running: Arc<AtomicBool>) -> Result<(), Box<dyn Error>> {
+        // custom handlers don't get connection options, only the default one uses them
+        let handler = match self.custom_handler {
+            true => self.handler,
+            false => options_handler(self.options.clone())
         };
 
-        RunningHttpServer::new(thread, running.clone())
+        if self.threads == 0 {
+            start_server_new_thread(self.http_server, listeners, self.options, self.limits, handler, running).await
+        } else {
+            start_server_with_workers(
+                self.http_server,
+                listeners,
+                self.options,
+                self.limits,
+                self.threads,
+                handler,
+                running,
+            ).await
+        }
     }
 }
@@ -0,0 +1,111 @@
+use super::{
+    HttpServer,
+    handler::{ConnectionOptions, Handler},
+    limits::ConnectionGuard,
+    listener::{Connection, StdConnection}
+};
+
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc
+    },
+    thread::JoinHandle
+};
+use tokio::{runtime::Builder, sync::mpsc, task::JoinSet};
+
+/// Worker thread with its own runtime
+struct Worker {
+    sender: mpsc::UnboundedSender<(StdConnection, ConnectionGuard)>,
+    active: Arc<AtomicUsize>,
+    thread: JoinHandle<()>
+}
+
+/// Pool of worker threads, each running a single-threaded runtime
+///
+/// Every connection is handled by the worker with the least active connections
+pub(crate) struct WorkerPool {
+    workers: Vec<Worker>
+}
+
+impl WorkerPool {
+    pub(crate) fn new<T: HttpServer + Send + 'static + Sync>(
+        threads: usize,
+        server: Arc<T>,
+        handler: Handler<T>,
+        options: ConnectionOptions
+    ) -> std::io::Result<Self> {
+        let handler = Arc::new(handler);
+        let mut workers = Vec::with_capacity(threads);
+
+        for i in 0..threads {
+            let runtime = Builder::new_current_thread().enable_all().build()?;
+            let (sender, receiver) = mpsc::unbounded_channel();
+            let active = Arc::new(AtomicUsize::new(0));
+
+            let server = server.clone();
+            let handler = handler.clone();
+            let options = options.clone();
+            let now_active = active.clone();
+
+            let thread = std::thread::Builder::new()
+                .name(format!("ezhttp-worker-{}", i))
+                .spawn(move || runtime.block_on(run_worker(server, handler, options, receiver, now_active)))?;
+
+            workers.push(Worker { sender, active, thread });
+        }
+
+        Ok(WorkerPool { workers })
+    }
+
+    /// Send connection to the least loaded worker
+    pub(crate) fn dispatch(&self, sock: Connection, guard: ConnectionGuard) {
+        let Ok(sock) = sock.into_std() else { return; };
+        let Some(worker) = self.workers.iter().min_by_key(|o| o.active.load(Ordering::Acquire)) else { return; };
+
+        worker.active.fetch_add(1, Ordering::AcqRel);
+        if worker.sender.send((sock, guard)).is_err() {
+            worker.active.fetch_sub(1, Ordering::AcqRel);
+        }
+    }
+
+    /// Stop accepting connections and wait for the workers to finish the current ones
+    pub(crate) async fn join(self) {
+        let threads: Vec<_> = self.workers.into_iter().map(|o| o.thread).collect();
+        let _ = tokio::task::spawn_blocking(move || {
+            for thread in threads {
+                let _ = thread.join();
+            }
+        }).await;
+    }
+}
+
+async fn run_worker<T: HttpServer + Send + 'static + Sync>(
+    server: Arc<T>,
+    handler: Arc<Handler<T>>,
+    options: ConnectionOptions,
+    mut receiver: mpsc::UnboundedReceiver<(StdConnection, ConnectionGuard)>,
+    active: Arc<AtomicUsize>
+) {
+    let mut tasks = JoinSet::new();
+
+    loop {
+        tokio::select! {
+            Some(_) = tasks.join_next(), if !tasks.is_empty() => {
+                active.fetch_sub(1, Ordering::AcqRel);
+            }
+            connection = receiver.recv() => {
+                let Some((sock, guard)) = connection else { break; };
+                let Ok(sock) = sock.into_tokio() else {
+                    active.fetch_sub(1, Ordering::AcqRel);
+                    continue;
+                };
+                let sock = sock.into_stream(options.timeout);
+
+                tasks.spawn(guard.hold(handler(server.clone(), sock)));
+            }
+        }
+    }
+
+    while tasks.join_next().await.is_some() {}
+}
@@ -0,0 +1,263 @@
+use ezhttp::{client::RequestBuilder, codec::*, prelude::*};
+
+#[test]
+fn request_head_partial() {
+    let data = b"GET /dku?key=value HTTP/1.1\r\nHost: meex.lol\r\nContent-Length: 4\r\n\r\nbody";
+    let head_end = data.len() - 4;
+
+    for i in 0..head_end {
+        assert!(parse_request_head(&data[..i]).unwrap().is_none());
+    }
+
+    let (head, size) = parse_request_head(data).unwrap().unwrap();
+    assert_eq!(size, head_end);
+    assert_eq!(head.method, "GET");
+    assert_eq!(head.target, "/dku?key=value");
+    assert_eq!(head.version, "HTTP/1.1");
+    assert_eq!(head.headers.get("host"), vec!["meex.lol".to_string()]);
+    assert_eq!(BodyFraming::from_headers(&head.headers).unwrap(), BodyFraming::Length(4));
+}
+
+#[test]
+fn response_head() {
+    let (head, _) = parse_response_head(b"HTTP/1.1 404 Not Found\r\nServer:ezhttp\r\n\r\n").unwrap().unwrap();
+    assert_eq!(head.version, "HTTP/1.1");
+    assert_eq!(head.status_code, "404 Not Found");
+    assert_eq!(head.headers.get("server"), vec!["ezhttp".to_string()]);
+    assert!(parse_response_head(b"HTTP/1.1\r\n\r\n").is_err());
+}
+
+#[test]
+fn chunked_body_bytewise() {
+    let data = b"4\r\nWiki\r\n7\r\npedia i\r\n0\r\n\r\nGET /";
+    let mut decoder = BodyDecoder::new(BodyFraming::Chunked);
+    let mut pos = 0;
+
+    while !decoder.is_done() {
+        assert!(decoder.hint() > 0);
+        pos += decoder.decode(&data[pos..pos + 1]).unwrap();
+    }
+
+    assert_eq!(&data[pos..], b"GET /");
+    assert_eq!(decoder.into_body().as_text().unwrap(), "Wikipedia i");
+}
+
+#[test]
+fn length_body_stops_at_end() {
+    let mut decoder = BodyDecoder::new(BodyFraming::Length(3));
+    assert_eq!(decoder.decode(b"abcdef").unwrap(), 3);
+    assert!(decoder.is_done());
+    assert_eq!(decoder.into_body().as_text().unwrap(), "abc");
+}
+
+#[test]
+fn response_round_trip() {
+    let response = HttpResponse::new(
+        OK,
+        Headers::from(vec![("Content-Length", "5")]),
+        Body::from_text("hello")
+    );
+
+    let mut data = Vec::new();
+    encode_response(&response, &mut data);
+    assert_eq!(data, b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello");
+
+    let (head, size) = parse_response_head(&data).unwrap().unwrap();
+    let mut decoder = BodyDecoder::new(BodyFraming::from_headers(&head.headers).unwrap());
+    decoder.decode(&data[size..]).unwrap();
+    assert!(decoder.is_done());
+    assert_eq!(decoder.into_body().as_text().unwrap(), "hello");
+}
+
+#[tokio::test]
+async fn recv_over_duplex() {
+    let (mut client, mut server) = tokio::io::duplex(1024);
+
+    let request = RequestBuilder::post("http://localhost/echo")
+        .header("Content-Length", 4)
+        .text("ping")
+        .build()
+        .unwrap();
+    request.send(&mut client).await.unwrap();
+
+    let addr = "127.0.0.1:80".parse().unwrap();
+    let received = HttpRequest::recv(&mut server, &addr).await.unwrap();
+    assert_eq!(received.method, "POST");
+    assert_eq!(received.url.path, "/echo");
+    assert_eq!(received.body.as_text().unwrap(), "ping");
+}
+
+#[test]
+fn chunked_extensions_and_trailers() {
+    let data = b"5;name=value\r\nhello\r\n6 ; ext\r\n world\r\n0\r\nChecksum: abc\r\nExpires: never\r\n\r\n";
+    let mut decoder = BodyDecoder::new(BodyFraming::Chunked);
+    assert_eq!(decoder.decode(data).unwrap(), data.len());
+    assert!(decoder.is_done());
+
+    let (body, trailers) = decoder.into_parts();
+    assert_eq!(body.as_text().unwrap(), "hello world");
+    assert_eq!(trailers.get("checksum"), vec!["abc".to_string()]);
+    assert_eq!(trailers.get("expires"), vec!["never".to_string()]);
+}
+
+#[test]
+fn invalid_chunk_size() {
+    for data in [&b"+5\r\nhello\r\n0\r\n\r\n"[..], b"\r\n", b"5\r\nhelloXX0\r\n\r\n"] {
+        assert!(BodyDecoder::new(BodyFraming::Chunked).decode(data).is_err());
+    }
+}
+
+#[test]
+fn framing_precedence() {
+    let headers = Headers::from(vec![("Content-Length", "10"), ("Transfer-Encoding", "gzip, chunked")]);
+    assert_eq!(BodyFraming::from_headers(&headers).unwrap(), BodyFraming::Chunked);
+    assert!(matches!(BodyFraming::from_request_headers(&headers), Err(HttpError::ConflictingFraming)));
+
+    let headers = Headers::from(vec![("Transfer-Encoding", "chunked, gzip")]);
+    assert_eq!(BodyFraming::from_headers(&headers).unwrap(), BodyFraming::Close);
+    assert!(matches!(BodyFraming::from_request_headers(&headers), Err(HttpError::InvalidTransferEncoding)));
+
+    let headers = Headers::from(vec![("Content-Length", "10"), ("Content-Length", "11")]);
+    assert!(matches!(BodyFraming::from_headers(&headers), Err(HttpError::ConflictingFraming)));
+
+    let headers = Headers::from(vec![("Content-Length", "+10")]);
+    assert!(matches!(BodyFraming::from_headers(&headers), Err(HttpError::InvalidContentSize)));
+}
+
+#[tokio::test]
+async fn recv_chunked_request() {
+    let (mut client, mut server) = tokio::io::duplex(1024);
+
+    tokio::io::AsyncWriteExt::write_all(&mut client, 
+        b"POST /upload HTTP/1.1\r\nTransfer-Encoding: chunked\r\nTrailer: Checksum\r\n\r\n\
+          3\r\nabc\r\n0\r\nChecksum: 123\r\n\r\n").await.unwrap();
+
+    let addr = "127.0.0.1:80".parse().unwrap();
+    let received = HttpRequest::recv(&mut server, &addr).await.unwrap();
+    assert_eq!(received.body.as_text().unwrap(), "abc");
+    assert_eq!(received.trailers.get("checksum"), vec!["123".to_string()]);
+}
+
+#[test]
+fn chunked_response_encoding() {
+    let mut response = HttpResponse::new(
+        OK,
+        Headers::from(vec![("Transfer-Encoding", "chunked"), ("Content-Length", "5")]),
+        Body::from_text("hello")
+    );
+    response.trailers.add("Checksum", "abc".to_string());
+
+    let mut data = Vec::new();
+    encode_response(&response, &mut data);
+    assert_eq!(data, b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nTrailer: Checksum\r\n\r\n\
+                       5\r\nhello\r\n0\r\nChecksum: abc\r\n\r\n");
+}
+
+#[tokio::test]
+async fn chunked_writer_round_trip() {
+    let (mut client, mut server) = tokio::io::duplex(1024);
+
+    let mut head = Vec::new();
+    encode_response_head(OK, &Headers::from(vec![("Transfer-Encoding", "chunked")]), &mut head);
+    tokio::io::AsyncWriteExt::write_all(&mut client, &head).await.unwrap();
+
+    let mut writer = ChunkedWriter::new(&mut client);
+    writer.write_chunk(b"streamed ").await.unwrap();
+    writer.write_chunk(b"").await.unwrap();
+    writer.write_chunk(b"body").await.unwrap();
+    writer.finish(&Headers::from(vec![("Checksum", "42")])).await.unwrap();
+
+    let response = HttpResponse::recv(&mut server).await.unwrap();
+    assert_eq!(response.body.as_text().unwrap(), "streamed body");
+    assert_eq!(response.trailers.get("checksum"), vec!["42".to_string()]);
+}
+
+#[test]
+fn bodiless_responses() {
+    let headers = Headers::from(vec![("Content-Length", "10")]);
+    assert_eq!(BodyFraming::from_response_head("200 OK", Some("HEAD"), &headers).unwrap(), BodyFraming::Empty);
+    assert_eq!(BodyFraming::from_response_head("204 No Content", None, &headers).unwrap(), BodyFraming::Empty);
+    assert_eq!(BodyFraming::from_response_head("304 Not Modified", Some("GET"), &headers).unwrap(), BodyFraming::Empty);
+    assert_eq!(BodyFraming::from_response_head("200 OK", Some("CONNECT"), &headers).unwrap(), BodyFraming::Empty);
+    assert_eq!(BodyFraming::from_response_head("200 OK", Some("GET"), &headers).unwrap(), BodyFraming::Length(10));
+}
+
+#[tokio::test]
+async fn response_without_length_lasts_until_close() {
+    assert_eq!(BodyFraming::from_response_head("200 OK", Some("GET"), &Headers::new()).unwrap(), BodyFraming::Close);
+    assert_eq!(BodyFraming::from_response_head("204 No Content", Some("GET"), &Headers::new()).unwrap(), BodyFraming::Empty);
+    assert_eq!(BodyFraming::from_request_headers(&Headers::new()).unwrap(), BodyFraming::Empty);
+
+    let mut data = &b"HTTP/1.0 200 OK\r\nServer: old\r\n\r\nbody until close"[..];
+    let response = HttpResponse::recv(&mut data).await.unwrap();
+    assert_eq!(response.body.as_text().unwrap(), "body until close");
+}
+
+#[tokio::test]
+async fn recv_skips_leading_empty_lines() {
+    let data = b"\r\n\r\nGET /first HTTP/1.1\r\nHost: meex.lol\r\n\r\n\r\nGET /second HTTP/1.1\r\nHost: meex.lol\r\n\r\n";
+    let mut stream = &data[..];
+    let addr = "127.0.0.1:80".parse().unwrap();
+
+    assert_eq!(HttpRequest::recv(&mut stream, &addr).await.unwrap().url.path, "/first");
+    assert_eq!(HttpRequest::recv(&mut stream, &addr).await.unwrap().url.path, "/second");
+}
+
+#[tokio::test]
+async fn recv_head_too_large() {
+    let mut data = b"GET / HTTP/1.1\r\n".to_vec();
+    while data.len() <= 64 * 1024 {
+        data.extend_from_slice(b"X-Filler: aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa\r\n");
+    }
+    data.extend_from_slice(b"\r\n");
+
+    let addr = "127.0.0.1:80".parse().unwrap();
+    assert!(matches!(HttpRequest::recv(&mut &data[..], &addr).await, Err(HttpError::HeadTooLarge)));
+    assert!(matches!(HttpResponse::recv(&mut &b"\r\n"[..]).await, Err(HttpError::ReadLineEof)));
+}
+
+#[test]
+fn chunked_lines_too_large() {
+    let mut decoder = BodyDecoder::new(BodyFraming::Chunked);
+    let line = vec![b'0'; 1024];
+    let mut result = Ok(0);
+    for _ in 0..65 {
+        result = decoder.decode(&line);
+        if result.is_err() {
+            break;
+        }
+    }
+    assert!(matches!(result, Err(HttpError::HeadTooLarge)));
+
+    let mut decoder = BodyDecoder::new(BodyFraming::Chunked);
+    decoder.decode(b"0\r\n").unwrap();
+    let mut result = Ok(0);
+    for _ in 0..2000 {
+        result = decoder.decode(b"X-Filler: aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa\r\n");
+        if result.is_err() {
+            break;
+        }
+    }
+    assert!(matches!(result, Err(HttpError::HeadTooLarge)));
+    assert!(!decoder.is_done());
+}
+
+#[tokio::test]
+async fn streamed_request_round_trip() {
+    let (mut client, mut server) = tokio::io::duplex(1024);
+
+    let request = RequestBuilder::post("http://localhost/upload")
+        .stream(ReaderSource(&b"read from reader"[..]))
+        .trailer("Checksum", "abc")
+        .build()
+        .unwrap();
+    assert!(request.body.is_stream());
+    assert_eq!(request.headers.get("transfer-encoding"), vec!["chunked".to_string()]);
+
+    tokio::spawn(async move { request.send(&mut client).await.unwrap() });
+
+    let addr = "127.0.0.1:80".parse().unwrap();
+    let received = HttpRequest::recv(&mut server, &addr).await.unwrap();
+    assert_eq!(received.body.as_text().unwrap(), "read from reader");
+    assert_eq!(received.trailers.get("checksum"), vec!["abc".to_string()]);
+}
@@ -0,0 +1,544 @@
+use std::{sync::{Arc, Mutex}, time::Duration};
+
+use async_trait::async_trait;
+use ezhttp::{client::{ClientBuilder, RequestBuilder}, prelude::*, Sendable};
+use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::TcpStream};
+
+struct EchoServer;
+
+/// Streams data of the channel, with its size in trailer
+struct CountingSource(tokio::sync::mpsc::Receiver<Vec<u8>>, usize);
+
+#[async_trait]
+impl BodySource for CountingSource {
+    async fn next_chunk(&mut self) -> Result<Option<Vec<u8>>, HttpError> {
+        let chunk = self.0.recv().await;
+        self.1 += chunk.as_ref().map_or(0, |o| o.len());
+        Ok(chunk)
+    }
+
+    fn trailers(&mut self) -> Headers {
+        Headers::from(vec![("Body-Size", self.1.to_string())])
+    }
+}
+
+#[async_trait]
+impl HttpServer for EchoServer {
+    async fn on_request(&self, req: &HttpRequest) -> Option<Box<dyn Sendable>> {
+        if let Some(millis) = req.url.path.strip_prefix("/sleep/") {
+            tokio::time::sleep(Duration::from_millis(millis.parse().ok()?)).await;
+            return Some(HttpResponse::new(
+                OK,
+                Headers::from(vec![("Content-Length", req.url.path.len().to_string())]),
+                Body::from_text(&req.url.path)
+            ).as_box());
+        }
+
+        if req.url.path == "/stream" {
+            let (sender, receiver) = tokio::sync::mpsc::channel(4);
+            tokio::spawn(async move {
+                for chunk in ["streamed ", "body"] {
+                    sender.send(chunk.as_bytes().to_vec()).await.unwrap();
+                }
+            });
+            return Some(HttpResponse::new(OK, Headers::new(), Body::from_stream(CountingSource(receiver, 0))).as_box());
+        }
+
+        if req.url.path == "/unframed" {
+            return Some(HttpResponse::new(OK, Headers::new(), Body::from_text("no length")).as_box());
+        }
+
+        if req.url.path == "/chunked" {
+            let headers = Headers::from(vec![("Transfer-Encoding", "chunked")]);
+            return Some(HttpResponse::new(OK, headers, Body::from_text("chunked body")).as_box());
+        }
+
+        if let Some(millis) = req.url.path.strip_prefix("/block/") {
+            std::thread::sleep(Duration::from_millis(millis.parse().ok()?));
+            return Some(HttpResponse::new(OK, Headers::new(), Body::from_text(&req.url.path)).as_box());
+        }
+
+        if req.url.path == "/local" {
+            let addr = req.local_addr?.to_string();
+            return Some(HttpResponse::new(
+                OK,
+                Headers::from(vec![("Content-Length", addr.len().to_string())]),
+                Body::from_text(&addr)
+            ).as_box());
+        }
+
+        Some(HttpResponse::new(
+            OK,
+            Headers::from(vec![("Content-Length", req.body.data.len().to_string())]),
+            req.body.clone()
+        ).as_box())
+    }
+
+    async fn on_expect_continue(&self, req: &HttpRequest) -> Option<Box<dyn Sendable>> {
+        if req.url.path == "/reject" {
+            Some(HttpResponse::new("413 Content Too Large", Headers::new(), Body::default()).as_box())
+        } else {
+            None
+        }
+    }
+
+    async fn on_early_hints(&self, req: &HttpRequest) -> Option<Headers> {
+        if req.url.path == "/hints" {
+            Some(Headers::from(vec![("Link", "</style.css>; rel=preload; as=style")]))
+        } else {
+            None
+        }
+    }
+
+    async fn on_start(&self, _: &str) {}
+    async fn on_close(&self) {}
+}
+
+async fn start(host: &str) -> RunningHttpServer {
+    start_with(HttpServerStarter::new(EchoServer, host)).await
+}
+
+async fn start_with(starter: HttpServerStarter<EchoServer>) -> RunningHttpServer {
+    let server = starter
+        .timeout(Some(Duration::from_secs(5)))
+        .start();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    server
+}
+
+async fn read_response(stream: &mut TcpStream) -> HttpResponse {
+    HttpResponse::recv(stream).await.unwrap()
+}
+
+#[tokio::test]
+async fn expect_continue() {
+    let server = start("127.0.0.1:18401").await;
+    let mut stream = TcpStream::connect("127.0.0.1:18401").await.unwrap();
+
+    stream.write_all(b"POST /upload HTTP/1.1\r\nContent-Length: 4\r\nExpect: 100-continue\r\n\r\n").await.unwrap();
+    let interim = HttpResponse::recv_head(&mut stream).await.unwrap();
+    assert_eq!(interim.status_code, CONTINUE);
+
+    stream.write_all(b"data").await.unwrap();
+    let response = read_response(&mut stream).await;
+    assert_eq!(response.status_code, OK);
+    assert_eq!(response.body.as_text().unwrap(), "data");
+
+    server.close();
+}
+
+#[tokio::test]
+async fn expect_continue_rejected() {
+    let server = start("127.0.0.1:18402").await;
+    let mut stream = TcpStream::connect("127.0.0.1:18402").await.unwrap();
+
+    stream.write_all(b"POST /reject HTTP/1.1\r\nContent-Length: 4\r\nExpect: 100-continue\r\n\r\n").await.unwrap();
+    let response = read_response(&mut stream).await;
+    assert_eq!(response.code(), Some(413));
+    assert_eq!(response.headers.get("connection"), vec!["close".to_string()]);
+    assert_eq!(response.headers.get("content-length"), vec!["0".to_string()]);
+
+    let mut rest = Vec::new();
+    stream.read_to_end(&mut rest).await.unwrap();
+    assert!(rest.is_empty());
+
+    server.close();
+}
+
+#[tokio::test]
+async fn client_expect_continue() {
+    let server = start("127.0.0.1:18403").await;
+    let client = ClientBuilder::new()
+        .expect_continue(0)
+        .expect_continue_timeout(Duration::from_secs(5))
+        .build();
+
+    let response = client.send(RequestBuilder::post("http://127.0.0.1:18403/upload").text("hello")).await.unwrap();
+    assert_eq!(response.body.as_text().unwrap(), "hello");
+
+    let response = client.send(RequestBuilder::post("http://127.0.0.1:18403/reject").text("hello")).await.unwrap();
+    assert_eq!(response.code(), Some(413));
+
+    let response = client.send(RequestBuilder::get("http://127.0.0.1:18403/reject")).await.unwrap();
+    assert_eq!(response.code(), Some(200));
+
+    server.close();
+}
+
+#[tokio::test]
+async fn early_hints() {
+    let server = start("127.0.0.1:18404").await;
+    let hints = Arc::new(Mutex::new(Vec::new()));
+    let hints_clone = hints.clone();
+    let client = ClientBuilder::new()
+        .interim_handler(move |o| hints_clone.lock().unwrap().push(o.clone()))
+        .build();
+
+    let response = client.send(RequestBuilder::post("http://127.0.0.1:18404/hints").text("hello")).await.unwrap();
+    assert_eq!(response.status_code, OK);
+    assert_eq!(response.body.as_text().unwrap(), "hello");
+
+    let hints = hints.lock().unwrap();
+    assert_eq!(hints.len(), 1);
+    assert_eq!(hints[0].status_code, EARLY_HINTS);
+    assert_eq!(hints[0].headers.get("link"), vec!["</style.css>; rel=preload; as=style".to_string()]);
+
+    server.close();
+}
+
+#[tokio::test]
+async fn head_request() {
+    let server = start("127.0.0.1:18405").await;
+    let mut stream = TcpStream::connect("127.0.0.1:18405").await.unwrap();
+
+    stream.write_all(b"HEAD / HTTP/1.1\r\nContent-Length: 5\r\n\r\nhelloGET / HTTP/1.1\r\nContent-Length: 2\r\n\r\nhi").await.unwrap();
+
+    let response = HttpResponse::recv_final(&mut stream, Some("HEAD"), |_| {}).await.unwrap();
+    assert_eq!(response.headers.get("content-length"), vec!["5".to_string()]);
+    assert!(response.body.data.is_empty());
+
+    let response = read_response(&mut stream).await;
+    assert_eq!(response.body.as_text().unwrap(), "hi");
+
+    let client = ClientBuilder::new().build();
+    let response = client.send(RequestBuilder::head("http://127.0.0.1:18405/")).await.unwrap();
+    assert_eq!(response.status_code, OK);
+    assert!(response.body.data.is_empty());
+
+    server.close();
+}
+
+async fn pipelined(host: &str) {
+    let mut stream = TcpStream::connect(host).await.unwrap();
+
+    stream.write_all(b"GET /sleep/200 HTTP/1.1\r\n\r\n\
+                       POST /echo HTTP/1.1\r\nContent-Length: 5\r\n\r\nhello\
+                       GET /sleep/50 HTTP/1.1\r\n\r\n").await.unwrap();
+
+    assert_eq!(read_response(&mut stream).await.body.as_text().unwrap(), "/sleep/200");
+    assert_eq!(read_response(&mut stream).await.body.as_text().unwrap(), "hello");
+    assert_eq!(read_response(&mut stream).await.body.as_text().unwrap(), "/sleep/50");
+}
+
+#[tokio::test]
+async fn pipelining_sequential() {
+    let server = start("127.0.0.1:18406").await;
+    pipelined("127.0.0.1:18406").await;
+    server.close();
+}
+
+#[tokio::test]
+async fn pipelining_concurrent() {
+    let server = start_with(HttpServerStarter::new(EchoServer, "127.0.0.1:18407").pipeline_limit(0)).await;
+
+    let started = std::time::Instant::now();
+    pipelined("127.0.0.1:18407").await;
+    assert!(started.elapsed() < Duration::from_millis(250));
+
+    server.close();
+}
+
+#[tokio::test]
+async fn connection_close() {
+    let server = start("127.0.0.1:18408").await;
+
+    let mut stream = TcpStream::connect("127.0.0.1:18408").await.unwrap();
+    stream.write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\nGET / HTTP/1.1\r\n\r\n").await.unwrap();
+    let response = read_response(&mut stream).await;
+    assert_eq!(response.headers.get("connection"), vec!["close".to_string()]);
+    let mut rest = Vec::new();
+    stream.read_to_end(&mut rest).await.unwrap();
+    assert!(rest.is_empty());
+
+    let mut stream = TcpStream::connect("127.0.0.1:18408").await.unwrap();
+    stream.write_all(b"GET / HTTP/1.0\r\n\r\n").await.unwrap();
+    let response = read_response(&mut stream).await;
+    assert_eq!(response.headers.get("connection"), vec!["close".to_string()]);
+
+    let mut stream = TcpStream::connect("127.0.0.1:18408").await.unwrap();
+    stream.write_all(b"GET / HTTP/1.0\r\nConnection: keep-alive\r\n\r\nGET / HTTP/1.0\r\n\r\n").await.unwrap();
+    let response = read_response(&mut stream).await;
+    assert_eq!(response.headers.get("connection"), vec!["keep-alive".to_string()]);
+    let response = read_response(&mut stream).await;
+    assert_eq!(response.headers.get("connection"), vec!["close".to_string()]);
+
+    server.close();
+}
+
+#[tokio::test]
+async fn keep_alive_limits() {
+    let server = start_with(HttpServerStarter::new(EchoServer, "127.0.0.1:18409")
+        .keep_alive_timeout(Some(Duration::from_millis(200)))
+        .max_requests(Some(2))).await;
+
+    let mut stream = TcpStream::connect("127.0.0.1:18409").await.unwrap();
+    stream.write_all(b"GET / HTTP/1.1\r\n\r\n").await.unwrap();
+    let response = read_response(&mut stream).await;
+    assert_eq!(response.headers.get("connection"), vec!["keep-alive".to_string()]);
+    assert_eq!(response.headers.get("keep-alive"), vec!["timeout=1, max=1".to_string()]);
+
+    stream.write_all(b"GET / HTTP/1.1\r\n\r\nGET / HTTP/1.1\r\n\r\n").await.unwrap();
+    let response = read_response(&mut stream).await;
+    assert_eq!(response.headers.get("connection"), vec!["close".to_string()]);
+    let mut rest = Vec::new();
+    stream.read_to_end(&mut rest).await.unwrap();
+    assert!(rest.is_empty());
+
+    let mut stream = TcpStream::connect("127.0.0.1:18409").await.unwrap();
+    stream.write_all(b"GET / HTTP/1.1\r\n\r\n").await.unwrap();
+    read_response(&mut stream).await;
+    let started = std::time::Instant::now();
+    stream.read_to_end(&mut rest).await.unwrap();
+    assert!(rest.is_empty());
+    assert!(started.elapsed() < Duration::from_secs(1));
+
+    server.close();
+}
+
+#[tokio::test]
+async fn restart_with_time_wait() {
+    let server = start("127.0.0.1:18417").await;
+    let mut stream = TcpStream::connect("127.0.0.1:18417").await.unwrap();
+    stream.write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n").await.unwrap();
+    let mut rest = Vec::new();
+    stream.read_to_end(&mut rest).await.unwrap();
+    server.close();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    // server closed the connection first, so its side is in TIME_WAIT now
+    let server = HttpServerStarter::new(EchoServer, "127.0.0.1:18417").try_start().unwrap();
+    server.close();
+}
+
+#[tokio::test]
+async fn keep_alive_slow_response() {
+    let server = start_with(HttpServerStarter::new(EchoServer, "127.0.0.1:18416")
+        .pipeline_limit(0)
+        .keep_alive_timeout(Some(Duration::from_millis(200)))).await;
+
+    let mut stream = TcpStream::connect("127.0.0.1:18416").await.unwrap();
+    stream.write_all(b"GET /sleep/400 HTTP/1.1\r\n\r\n").await.unwrap();
+    let response = read_response(&mut stream).await;
+    assert_eq!(response.body.as_text().unwrap(), "/sleep/400");
+    assert_eq!(response.headers.get("keep-alive"), vec!["timeout=1".to_string()]);
+
+    stream.write_all(b"GET /sleep/0 HTTP/1.1\r\n\r\n").await.unwrap();
+    assert_eq!(read_response(&mut stream).await.body.as_text().unwrap(), "/sleep/0");
+
+    server.close();
+}
+
+#[tokio::test]
+async fn connection_limits() {
+    let server = start_with(HttpServerStarter::new(EchoServer, "127.0.0.1:18410")
+        .max_connections_per_ip(Some(1))).await;
+
+    let mut first = TcpStream::connect("127.0.0.1:18410").await.unwrap();
+    let mut second = TcpStream::connect("127.0.0.1:18410").await.unwrap();
+    assert_eq!(read_response(&mut second).await.status_code, SERVICE_UNAVAILABLE);
+
+    // request sent before the rejection is read by the server, so the 503 isn't lost to a reset
+    let mut second = TcpStream::connect("127.0.0.1:18410").await.unwrap();
+    second.write_all(format!("GET / HTTP/1.1\r\nX-Filler: {}\r\n\r\n", "a".repeat(4096)).as_bytes()).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(read_response(&mut second).await.status_code, SERVICE_UNAVAILABLE);
+    let mut rest = Vec::new();
+    second.read_to_end(&mut rest).await.unwrap();
+
+    first.write_all(b"GET / HTTP/1.1\r\n\r\n").await.unwrap();
+    assert_eq!(read_response(&mut first).await.status_code, OK);
+    drop(first);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let mut third = TcpStream::connect("127.0.0.1:18410").await.unwrap();
+    third.write_all(b"GET / HTTP/1.1\r\n\r\n").await.unwrap();
+    assert_eq!(read_response(&mut third).await.status_code, OK);
+
+    server.close();
+}
+
+#[tokio::test]
+async fn connection_backpressure() {
+    let server = start_with(HttpServerStarter::new(EchoServer, "127.0.0.1:18411")
+        .max_connections(Some(1))).await;
+
+    let mut first = TcpStream::connect("127.0.0.1:18411").await.unwrap();
+    first.write_all(b"GET / HTTP/1.1\r\n\r\n").await.unwrap();
+    assert_eq!(read_response(&mut first).await.status_code, OK);
+
+    let mut second = TcpStream::connect("127.0.0.1:18411").await.unwrap();
+    second.write_all(b"GET / HTTP/1.1\r\n\r\n").await.unwrap();
+    let waiting = tokio::time::timeout(Duration::from_millis(200), read_response(&mut second)).await;
+    assert!(waiting.is_err());
+
+    drop(first);
+    assert_eq!(read_response(&mut second).await.status_code, OK);
+
+    server.close();
+}
+
+#[tokio::test]
+async fn worker_threads() {
+    let server = start_with(HttpServerStarter::new(EchoServer, "127.0.0.1:18412").threads(2)).await;
+
+    // handler blocks its worker thread, so requests are processed at once only by different workers
+    let started = std::time::Instant::now();
+    let requests = (0..2).map(|_| tokio::spawn(async {
+        let mut stream = TcpStream::connect("127.0.0.1:18412").await.unwrap();
+        stream.write_all(b"GET /block/300 HTTP/1.1\r\n\r\n").await.unwrap();
+        read_response(&mut stream).await.body.as_text().unwrap()
+    })).collect::<Vec<_>>();
+
+    for request in requests {
+        assert_eq!(request.await.unwrap(), "/block/300");
+    }
+    assert!(started.elapsed() < Duration::from_millis(550));
+
+    server.close();
+}
+
+#[tokio::test]
+async fn single_worker_thread() {
+    let server = start_with(HttpServerStarter::new(EchoServer, "127.0.0.1:18415").threads(1)).await;
+
+    // idle kept alive connection doesn't block other connections
+    let mut idle = TcpStream::connect("127.0.0.1:18415").await.unwrap();
+    idle.write_all(b"GET /first HTTP/1.1\r\n\r\n").await.unwrap();
+    read_response(&mut idle).await;
+
+    let mut stream = TcpStream::connect("127.0.0.1:18415").await.unwrap();
+    stream.write_all(b"POST / HTTP/1.1\r\nContent-Length: 2\r\n\r\nhi").await.unwrap();
+    let response = tokio::time::timeout(Duration::from_secs(1), read_response(&mut stream)).await.unwrap();
+    assert_eq!(response.body.as_text().unwrap(), "hi");
+
+    server.close();
+}
+
+#[tokio::test]
+async fn multiple_listeners() {
+    let server = start_with(HttpServerStarter::new(EchoServer, "127.0.0.1:0").bind("127.0.0.1:0")).await;
+
+    let addrs = server.local_addrs().to_vec();
+    assert_eq!(addrs.len(), 2);
+    assert_ne!(addrs[0].port(), 0);
+    assert_ne!(addrs[0], addrs[1]);
+
+    for addr in addrs {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(b"GET /local HTTP/1.1\r\n\r\n").await.unwrap();
+        assert_eq!(read_response(&mut stream).await.body.as_text().unwrap(), addr.to_string());
+    }
+
+    let taken = server.local_addrs()[0].to_string();
+    let result = HttpServerStarter::new(EchoServer, "127.0.0.1:0").bind(&taken).try_start();
+    assert_eq!(result.err().unwrap().kind(), std::io::ErrorKind::AddrInUse);
+
+    server.close();
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn unix_socket() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let path = std::env::temp_dir().join(format!("ezhttp-test-{}.sock", std::process::id()));
+    drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+    assert!(path.exists());
+
+    let server = start_with(HttpServerStarter::new(EchoServer, &format!("unix:{}", path.display()))
+        .unix_permissions(Some(0o600))
+        .unix_cleanup(true)).await;
+    assert!(server.local_addrs().is_empty());
+    assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+
+    let mut stream = tokio::net::UnixStream::connect(&path).await.unwrap();
+    stream.write_all(b"POST / HTTP/1.1\r\nContent-Length: 5\r\n\r\nhello").await.unwrap();
+    let response = HttpResponse::recv(&mut stream).await.unwrap();
+    assert_eq!(response.body.as_text().unwrap(), "hello");
+
+    server.close();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(!path.exists());
+
+    std::fs::write(&path, b"not a socket").unwrap();
+    let result = HttpServerStarter::new(EchoServer, &format!("unix:{}", path.display()))
+        .unix_cleanup(true)
+        .try_start();
+    assert_eq!(result.err().unwrap().kind(), std::io::ErrorKind::AlreadyExists);
+    assert_eq!(std::fs::read(&path).unwrap(), b"not a socket");
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn client_unix_socket() {
+    let path = std::env::temp_dir().join(format!("ezhttp-client-{}.sock", std::process::id()));
+    let server = start_with(HttpServerStarter::new(EchoServer, &format!("unix:{}", path.display()))
+        .unix_cleanup(true)).await;
+
+    let client = ClientBuilder::new().unix_socket(&path).build();
+    let response = client.send(RequestBuilder::post("http://localhost/echo").text("hello")).await.unwrap();
+    assert_eq!(response.body.as_text().unwrap(), "hello");
+
+    server.close();
+}
+
+#[tokio::test]
+async fn streamed_bodies() {
+    let server = start("127.0.0.1:18413").await;
+
+    let mut stream = TcpStream::connect("127.0.0.1:18413").await.unwrap();
+    stream.write_all(b"GET /stream HTTP/1.1\r\n\r\n").await.unwrap();
+    let response = read_response(&mut stream).await;
+    assert_eq!(response.headers.get("transfer-encoding"), vec!["chunked".to_string()]);
+    assert_eq!(response.body.as_text().unwrap(), "streamed body");
+    assert_eq!(response.trailers.get("body-size"), vec!["13".to_string()]);
+
+    let mut stream = TcpStream::connect("127.0.0.1:18413").await.unwrap();
+    stream.write_all(b"GET /stream HTTP/1.0\r\nConnection: keep-alive\r\n\r\n").await.unwrap();
+    let mut data = String::new();
+    stream.read_to_string(&mut data).await.unwrap();
+    let (head, body) = data.split_once("\r\n\r\n").unwrap();
+    assert!(!head.to_lowercase().contains("transfer-encoding"));
+    assert!(head.split("\r\n").any(|o| o == "Connection: close"));
+    assert_eq!(body, "streamed body");
+
+    let mut stream = TcpStream::connect("127.0.0.1:18413").await.unwrap();
+    stream.write_all(b"GET /chunked HTTP/1.0\r\n\r\n").await.unwrap();
+    let response = read_response(&mut stream).await;
+    assert!(response.headers.get("transfer-encoding").is_empty());
+    assert_eq!(response.headers.get("content-length"), vec!["12".to_string()]);
+    assert_eq!(response.body.as_text().unwrap(), "chunked body");
+
+    let (sender, receiver) = tokio::sync::mpsc::channel(4);
+    tokio::spawn(async move {
+        for chunk in ["uploaded ", "in ", "parts"] {
+            sender.send(chunk.as_bytes().to_vec()).await.unwrap();
+        }
+    });
+    let client = ClientBuilder::new().build();
+    let response = client.send(RequestBuilder::post("http://127.0.0.1:18413/echo").stream(receiver)).await.unwrap();
+    assert_eq!(response.body.as_text().unwrap(), "uploaded in parts");
+
+    server.close();
+}
+
+#[tokio::test]
+async fn unframed_responses() {
+    let server = start("127.0.0.1:18414").await;
+    let mut stream = TcpStream::connect("127.0.0.1:18414").await.unwrap();
+
+    stream.write_all(b"GET /unframed HTTP/1.1\r\n\r\nHEAD /unframed HTTP/1.1\r\n\r\nGET /unframed HTTP/1.1\r\n\r\n").await.unwrap();
+
+    let response = read_response(&mut stream).await;
+    assert_eq!(response.headers.get("content-length"), vec!["9".to_string()]);
+    assert_eq!(response.body.as_text().unwrap(), "no length");
+
+    let response = HttpResponse::recv_final(&mut stream, Some("HEAD"), |_| {}).await.unwrap();
+    assert_eq!(response.headers.get("content-length"), vec!["9".to_string()]);
+
+    let response = read_response(&mut stream).await;
+    assert_eq!(response.body.as_text().unwrap(), "no length");
+
+    server.close();
+}